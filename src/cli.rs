@@ -1,21 +1,219 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use comunica_link_queue_parser_rs::filter::TimeBound;
+use regex::Regex;
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 /// A small CLI tool to parse the occupancy of the link queue of the comunica link traversal query engine
 pub(crate) struct Cli {
-    /// Path of logging file of comunica
-    /// By default the value is [default: ./info]
-    #[arg(short, long)]
-    pub input: Option<PathBuf>,
+    /// Path of logging file of comunica, `-` reads the standard input.
+    /// Can be repeated, and be a directory or a glob pattern, to parse several runs
+    /// By default the standard input is read when it is piped, otherwise the value is [default: ./info]
+    #[arg(short, long, global = true)]
+    pub input: Vec<PathBuf>,
+
+    /// Identifier of the run of each input, in the order of the inputs
+    /// By default the runs are identified by the file name of their log when there are several inputs
+    #[arg(short, long, global = true)]
+    pub label: Vec<String>,
+
+    /// Write one output per input, named after the output with the label of the run before its extension [default: false]
+    #[arg(long, global = true, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    pub per_input: bool,
 
     /// Path of the output occupancy file
     /// By default the value is [default: ./occupancy.json]
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub output: Option<PathBuf>,
 
+    /// JSON object naming the queries, keyed by query id or by query text, the names are added to the query table
+    #[arg(long, global = true, value_name = "FILE")]
+    pub query_names: Option<PathBuf>,
+
+    /// Also write a self-contained HTML report with charts of the occupancy and of the push and pop rates,
+    /// the counters of each actor and a searchable table of the events of every query
+    #[arg(long, global = true, value_name = "FILE")]
+    pub html: Option<PathBuf>,
+
     /// Print the occupancy object [default: false]
-    #[arg(short, long,default_value_t=false, action = clap::ArgAction::SetTrue)]
+    #[arg(short, long, global = true, default_value_t = false, action = clap::ArgAction::SetTrue)]
     pub print: bool,
+
+    /// Keep reading the input as comunica appends to it, the output is rewritten after each refresh [default: false]
+    #[arg(short = 'F', long, global = true, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    pub follow: bool,
+
+    /// Interval between two reads of the input in follow mode, in milliseconds
+    #[arg(long, global = true, default_value_t = 1000)]
+    pub refresh: u64,
+
+    /// Format of the log, the JSON records of `@comunica/logger-bunyan` or the records of `@comunica/logger-pretty`,
+    /// by default the format of each record is detected
+    #[arg(long, global = true, value_enum, default_value_t = InputFormat::Auto)]
+    pub log_format: InputFormat,
+
+    /// Start a new execution of a query when two of its events are more than this many milliseconds apart.
+    /// By default the executions are only told apart by their process and by their counters restarting
+    #[arg(long, global = true, value_name = "MS")]
+    pub execution_gap: Option<u64>,
+
+    /// Stop at the first link queue event that cannot be parsed and exit with an error [default: false]
+    #[arg(long, global = true, default_value_t = false, action = clap::ArgAction::SetTrue)]
+    pub fail_on_error: bool,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Only the link queue events matching every given filter are kept
+#[derive(Args)]
+#[command(next_help_heading = "Filters")]
+pub(crate) struct FilterArgs {
+    /// Regular expression matching the one-line text of the query
+    #[arg(long, global = true, value_name = "REGEX")]
+    pub query: Option<Regex>,
+
+    /// Id of the query, can be repeated
+    #[arg(long, global = true, value_name = "ID")]
+    pub query_id: Vec<String>,
+
+    /// Regular expression matching the name of the actor that produced the link, `unknown` when comunica does not report it
+    #[arg(long, global = true, value_name = "REGEX")]
+    pub actor: Option<Regex>,
+
+    /// Regular expression matching the url of the link
+    #[arg(long, global = true, value_name = "REGEX")]
+    pub url: Option<Regex>,
+
+    /// Regular expression matching the host of the url of the link
+    #[arg(long, global = true, value_name = "REGEX")]
+    pub host: Option<Regex>,
+
+    /// Type of the events
+    #[arg(long, global = true, value_enum)]
+    pub event_type: Option<QueueOperation>,
+
+    /// Start of the time window, inclusive, in milliseconds of `link.timestamp` or as an RFC 3339 date
+    /// compared with the time of the Bunyan records
+    #[arg(long, global = true, value_name = "TIME")]
+    pub from: Option<TimeBound>,
+
+    /// End of the time window, inclusive, in milliseconds of `link.timestamp` or as an RFC 3339 date
+    /// compared with the time of the Bunyan records
+    #[arg(long, global = true, value_name = "TIME")]
+    pub to: Option<TimeBound>,
+}
+
+#[derive(Subcommand)]
+pub(crate) enum Command {
+    /// Write each link queue event, tagged with the id of its query and its type, as a line of JSON as soon as it is read
+    /// By default the output is [default: ./occupancy.ndjson]
+    Stream,
+    /// Export the queue occupancy of each query as a series of push and pop events in log order
+    /// By default the output is [default: ./timeseries.<format>]
+    Timeseries {
+        /// Format of the series
+        #[arg(short, long, value_enum, default_value_t = TimeseriesFormat::Csv)]
+        format: TimeseriesFormat,
+    },
+    /// Export every link queue event as a flat CSV record with the id and the execution of its query, its type, url, parent,
+    /// producing actor, timestamp, queue size and a push and a pop column per actor
+    /// By default the output is [default: ./events.csv]
+    Events,
+    /// Write the table of the queries with their id, name and text
+    /// By default the output is [default: ./queries.csv]
+    Queries,
+    /// Append the runs to a SQLite database with normalized tables of the runs, queries, links, actors,
+    /// push and pop events and counters of each actor, the database is created if it does not exist
+    /// By default the output is [default: ./link-queue.sqlite]
+    Sqlite,
+    /// Pair the pushes and pops of each link and report how long the links waited in the queue
+    /// By default the output is [default: ./residence.json]
+    Residence,
+    /// Follow each popped link to its HTTP request and the identification of its source, and
+    /// report the latency of each host
+    /// By default the output is [default: ./latency.json]
+    Latency,
+    /// Rebuild the traversal tree of each query from the parent of the pushed links
+    /// By default the output is [default: ./tree.<format>]
+    Tree {
+        /// Format of the tree, json only reports the depth, branching factor and longest chains
+        #[arg(short, long, value_enum, default_value_t = TreeFormat::Json)]
+        format: TreeFormat,
+
+        /// Number of longest chains reported in the json format
+        #[arg(long, default_value_t = 5)]
+        chains: usize,
+    },
+    /// Report the number of events, the peak queue size, the duration, the distinct urls and hosts
+    /// and the counters of each actor per query
+    /// By default the summary is only printed
+    Summary {
+        /// Format of the summary
+        #[arg(short, long, value_enum, default_value_t = SummaryFormat::Table)]
+        format: SummaryFormat,
+    },
+    /// Plot the queue size of each query over time as an SVG image
+    /// By default the output is [default: ./plot.svg]
+    Plot {
+        /// Quantity of the horizontal axis, `link.timestamp` or the time of the Bunyan records
+        #[arg(long, value_enum, default_value_t = PlotAxis::Relative)]
+        axis: PlotAxis,
+
+        /// Also plot the cumulative pushes (solid) and pops (dashed) of each actor [default: false]
+        #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+        actors: bool,
+
+        /// Draw every query of every run in a single chart instead of one chart per query [default: false]
+        #[arg(long, default_value_t = false, action = clap::ArgAction::SetTrue)]
+        overlay: bool,
+    },
+    /// Show a live dashboard of the queue size, the counters of each actor, the recently popped urls
+    /// and the throughput of each query, the log is read from the standard input or followed with --follow
+    /// Nothing is written
+    Dashboard,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum TimeseriesFormat {
+    Csv,
+    Ndjson,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum TreeFormat {
+    Json,
+    Dot,
+    Graphml,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum SummaryFormat {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum PlotAxis {
+    /// Milliseconds of `link.timestamp`
+    Relative,
+    /// Wall-clock time of the `time` field of Bunyan
+    WallClock,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum QueueOperation {
+    Push,
+    Pop,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub(crate) enum InputFormat {
+    Auto,
+    Bunyan,
+    Pretty,
 }
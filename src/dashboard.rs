@@ -0,0 +1,445 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, Paragraph, Row, Sparkline, Table, Tabs, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::object::{EventType, HistoryKey, LinkEvent, QueryEvent};
+use crate::parser::LinkQueueParser;
+
+/// Number of queue sizes kept for the sparkline of a query
+const SIZE_HISTORY: usize = 4096;
+/// Number of recently popped urls kept for a query
+const RECENT_POPS: usize = 50;
+/// Window over which the throughput is computed, in the unit of `link.timestamp` (milliseconds)
+const THROUGHPUT_WINDOW: f64 = 1000.0;
+/// Number of lines of the log waiting for the dashboard, the reader blocks when they are not drawn yet
+pub const LINE_BUFFER: usize = 4096;
+/// Maximum number of lines parsed between two frames, so that a fast log does not hold the redraws
+const LINES_PER_FRAME: usize = 1024;
+
+/// Live state of the link queue of a query
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryState {
+    /// Queue size after each of the last events
+    pub sizes: VecDeque<u64>,
+    pub peak_size: u64,
+    pub pushes: usize,
+    pub pops: usize,
+    /// Counters of the last event
+    pub push_by_actor: BTreeMap<String, u64>,
+    pub pop_by_actor: BTreeMap<String, u64>,
+    /// Most recently popped urls, the latest first
+    pub recent_pops: VecDeque<String>,
+    /// `link.timestamp` of the pops within the throughput window
+    pop_timestamps: VecDeque<f64>,
+}
+
+impl QueryState {
+    pub fn add(&mut self, event_type: EventType, event: &LinkEvent) {
+        if self.sizes.len() == SIZE_HISTORY {
+            self.sizes.pop_front();
+        }
+        self.sizes.push_back(event.queue.size);
+        self.peak_size = self.peak_size.max(event.queue.size);
+        self.push_by_actor.clone_from(&event.queue.push);
+        self.pop_by_actor.clone_from(&event.queue.pop);
+
+        let timestamp = event.link.timestamp;
+        match event_type {
+            EventType::Push => self.pushes += 1,
+            EventType::Pop => {
+                self.pops += 1;
+                self.recent_pops.push_front(event.link.url.clone());
+                self.recent_pops.truncate(RECENT_POPS);
+                self.pop_timestamps.push_back(timestamp);
+            }
+        }
+        while self
+            .pop_timestamps
+            .front()
+            .is_some_and(|popped_at| *popped_at <= timestamp - THROUGHPUT_WINDOW)
+        {
+            self.pop_timestamps.pop_front();
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.sizes.back().copied().unwrap_or(0)
+    }
+
+    /// Number of links popped per second during the last second of the log
+    pub fn throughput(&self) -> f64 {
+        self.pop_timestamps.len() as f64 * 1000.0 / THROUGHPUT_WINDOW
+    }
+}
+
+/// State of the dashboard, the queries are shown in the order in which they appear in the log
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    parser: LinkQueueParser,
+    queries: Vec<(String, QueryState)>,
+    index: HashMap<HistoryKey, usize>,
+    selected: usize,
+    /// Whether the whole log was read
+    ended: bool,
+    error: Option<String>,
+}
+
+impl Dashboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a dashboard parsing the log with `parser`
+    pub fn with_parser(parser: LinkQueueParser) -> Self {
+        Self {
+            parser,
+            ..Self::default()
+        }
+    }
+
+    /// Parse the next line of the log, lines that are not link queue events are ignored
+    pub fn process_line(&mut self, line: &str) {
+        // malformed lines are skipped, and reported in strict mode, the events completed by the
+        // line are shown anyway
+        if let Err(error) = self.parser.queue_line(line) {
+            self.error.get_or_insert(error.to_string());
+        }
+        self.add_queued();
+    }
+
+    /// Close the record of the pretty logger left open at the end of the log, a truncated record
+    /// is reported in the footer
+    pub fn finish(&mut self) {
+        let errors = self.parser.errors().len();
+        let error = self.parser.finish().err();
+        if let Some(error) = error.as_ref().or(self.parser.errors().get(errors)) {
+            self.error = Some(error.to_string());
+        }
+        self.add_queued();
+    }
+
+    /// Parse at most `max` lines received from `lines`, returns whether more lines may be waiting
+    fn receive(&mut self, lines: &Receiver<io::Result<String>>, max: usize) -> bool {
+        for _ in 0..max {
+            if self.ended {
+                return false;
+            }
+            match lines.try_recv() {
+                Ok(Ok(line)) => self.process_line(&line),
+                Ok(Err(error)) => self.error = Some(error.to_string()),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    self.finish();
+                    self.ended = true;
+                }
+            }
+        }
+        !self.ended
+    }
+
+    fn add_queued(&mut self) {
+        while let Some(event) = self.parser.next_event() {
+            self.add(&event);
+        }
+    }
+
+    pub fn add(&mut self, event: &QueryEvent) {
+        let key = event.key();
+        let index = match self.index.get(&key) {
+            Some(index) => *index,
+            None => {
+                self.queries.push((key.to_string(), QueryState::default()));
+                self.index.insert(key, self.queries.len() - 1);
+                self.queries.len() - 1
+            }
+        };
+        self.queries[index].1.add(event.event_type, &event.event);
+    }
+
+    pub fn queries(&self) -> &[(String, QueryState)] {
+        &self.queries
+    }
+
+    pub fn select_next(&mut self) {
+        if !self.queries.is_empty() {
+            self.selected = (self.selected + 1) % self.queries.len();
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if !self.queries.is_empty() {
+            self.selected = (self.selected + self.queries.len() - 1) % self.queries.len();
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [tabs_area, header_area, sparkline_area, details_area, footer_area] =
+            Layout::vertical([
+                Constraint::Length(3),
+                Constraint::Length(4),
+                Constraint::Length(10),
+                Constraint::Min(5),
+                Constraint::Length(1),
+            ])
+            .areas(frame.area());
+
+        let diagnostics = self.parser.diagnostics();
+        let mut footer = format!(
+            "lines read: {} | parsed events: {} | skipped lines: {}",
+            diagnostics.lines,
+            diagnostics.parsed_events,
+            diagnostics.skipped_line_count()
+        );
+        if let Some(error) = &self.error {
+            footer.push_str(&format!(" | {error}"));
+        } else if self.ended {
+            footer.push_str(" | end of the log");
+        }
+        footer.push_str(" | ←/→: query, q: quit");
+        frame.render_widget(Line::from(footer).dim(), footer_area);
+
+        let Some((query, state)) = self.queries.get(self.selected) else {
+            frame.render_widget(
+                Paragraph::new("waiting for link queue events…")
+                    .block(Block::bordered().title("link queue")),
+                tabs_area,
+            );
+            return;
+        };
+
+        let titles = (0..self.queries.len()).map(|index| format!("#{index}"));
+        frame.render_widget(
+            Tabs::new(titles)
+                .select(self.selected)
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+                .block(Block::bordered().title("queries")),
+            tabs_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    "queue size: {} (peak {}) | pushes: {} | pops: {} | {:.1} links/s",
+                    state.size(),
+                    state.peak_size,
+                    state.pushes,
+                    state.pops,
+                    state.throughput()
+                ))
+                .bold(),
+                Line::from(query.as_str()),
+            ])
+            .wrap(Wrap { trim: true }),
+            header_area,
+        );
+
+        let block = Block::bordered().title("queue size");
+        // the sparkline is drawn from the left, so only the latest sizes that fit are given
+        let width = block.inner(sparkline_area).width as usize;
+        let sizes: Vec<u64> = state
+            .sizes
+            .iter()
+            .skip(state.sizes.len().saturating_sub(width))
+            .copied()
+            .collect();
+        frame.render_widget(
+            Sparkline::default().block(block).data(&sizes),
+            sparkline_area,
+        );
+
+        let [actors_area, pops_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(details_area);
+        let actors: BTreeSet<&String> = state
+            .push_by_actor
+            .keys()
+            .chain(state.pop_by_actor.keys())
+            .collect();
+        let rows = actors.into_iter().map(|actor| {
+            Row::new(vec![
+                actor.clone(),
+                state.push_by_actor.get(actor).unwrap_or(&0).to_string(),
+                state.pop_by_actor.get(actor).unwrap_or(&0).to_string(),
+            ])
+        });
+        frame.render_widget(
+            Table::new(
+                rows,
+                [
+                    Constraint::Fill(1),
+                    Constraint::Length(8),
+                    Constraint::Length(8),
+                ],
+            )
+            .header(Row::new(vec!["actor", "push", "pop"]).bold())
+            .block(Block::bordered().title("actors")),
+            actors_area,
+        );
+        frame.render_widget(
+            List::new(state.recent_pops.iter().map(String::as_str))
+                .block(Block::bordered().title("recently popped")),
+            pops_area,
+        );
+    }
+}
+
+/// Show the dashboard in the terminal until the user quits, the lines of the log are received
+/// from `lines`, parsed with `parser`, and the dashboard is redrawn every `refresh`
+pub fn run(
+    parser: LinkQueueParser,
+    lines: Receiver<io::Result<String>>,
+    refresh: Duration,
+) -> io::Result<()> {
+    let mut terminal = ratatui::init();
+    let result = run_loop(
+        &mut terminal,
+        Dashboard::with_parser(parser),
+        lines,
+        refresh,
+    );
+    ratatui::restore();
+    result
+}
+
+fn run_loop(
+    terminal: &mut DefaultTerminal,
+    mut dashboard: Dashboard,
+    lines: Receiver<io::Result<String>>,
+    refresh: Duration,
+) -> io::Result<()> {
+    loop {
+        let pending = dashboard.receive(&lines, LINES_PER_FRAME);
+        terminal.draw(|frame| dashboard.draw(frame))?;
+
+        // the lines left are parsed right after the keys are handled
+        let timeout = if pending { Duration::ZERO } else { refresh };
+        if event::poll(timeout)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Right | KeyCode::Down | KeyCode::Tab => dashboard.select_next(),
+                    KeyCode::Left | KeyCode::Up | KeyCode::BackTab => dashboard.select_previous(),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":1000.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/b","timestamp":1100.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":2,"push":{"actor-a":2},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://example.org/a","timestamp":1200.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":2},"pop":{"actor-a":1}}},"msg":"Link queue changed"}"#,
+        "\n",
+        "not a link queue event\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/c","timestamp":5.0},"query":"ASK { ?s ?p ?o }","queue":{"size":1,"push":{"actor-b":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://example.org/b","timestamp":2500.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":0,"push":{"actor-a":2},"pop":{"actor-a":2}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn should_track_the_state_of_each_query() {
+        let mut dashboard = Dashboard::new();
+
+        for line in LOG.lines() {
+            dashboard.process_line(line);
+        }
+
+        let queries = dashboard.queries();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].0, "SELECT * WHERE { ?s ?p ?o }");
+        assert_eq!(queries[1].0, "ASK { ?s ?p ?o }");
+        let state = &queries[0].1;
+        assert_eq!(state.sizes, vec![1, 2, 1, 0]);
+        assert_eq!((state.size(), state.peak_size), (0, 2));
+        assert_eq!((state.pushes, state.pops), (2, 2));
+        assert_eq!(
+            state.pop_by_actor,
+            BTreeMap::from([("actor-a".to_string(), 2)])
+        );
+        assert_eq!(
+            state.recent_pops,
+            vec!["https://example.org/b", "https://example.org/a"]
+        );
+        // the pop at 1200 is more than a second before the pop at 2500
+        assert_eq!(state.throughput(), 1.0);
+    }
+
+    #[test]
+    fn should_report_a_truncated_pretty_record_at_the_end_of_the_input() {
+        let mut dashboard = Dashboard::new();
+        dashboard.process_line("[2024-07-05T12:06:08.654Z]  TRACE: Link queue changed {");
+        dashboard.process_line("  data: { type: 'push', link: { url: 'https://example.org/a', timestamp: 1 }, query: 'ASK { ?s ?p ?o }', queue: { size: 1, push: {}, pop: {} } }");
+        assert_eq!(dashboard.error, None);
+
+        dashboard.finish();
+
+        assert!(dashboard.error.unwrap().contains("line 1"));
+    }
+
+    #[test]
+    fn should_parse_a_limited_number_of_lines_per_frame() {
+        let (sender, receiver) = mpsc::sync_channel(LINE_BUFFER);
+        for line in LOG.lines() {
+            sender.send(Ok(line.to_string())).unwrap();
+        }
+        drop(sender);
+        let mut dashboard = Dashboard::new();
+
+        assert!(dashboard.receive(&receiver, 2));
+        assert_eq!(dashboard.queries()[0].1.pushes, 2);
+        assert!(!dashboard.receive(&receiver, LINES_PER_FRAME));
+        assert!(dashboard.ended);
+        assert_eq!(dashboard.queries().len(), 2);
+    }
+
+    #[test]
+    fn should_cycle_through_the_queries() {
+        let mut dashboard = Dashboard::new();
+        for line in LOG.lines() {
+            dashboard.process_line(line);
+        }
+
+        dashboard.select_previous();
+        assert_eq!(dashboard.selected, 1);
+        dashboard.select_next();
+        assert_eq!(dashboard.selected, 0);
+    }
+
+    #[test]
+    fn should_draw_the_selected_query() {
+        let mut dashboard = Dashboard::new();
+        for line in LOG.lines() {
+            dashboard.process_line(line);
+        }
+        let mut terminal =
+            ratatui::Terminal::new(ratatui::backend::TestBackend::new(100, 30)).unwrap();
+
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let screen: String = buffer.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("queue size: 0 (peak 2) | pushes: 2 | pops: 2 | 1.0 links/s"));
+        assert!(screen.contains("https://example.org/b"));
+        assert!(screen.contains("actor-a"));
+    }
+}
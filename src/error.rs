@@ -0,0 +1,133 @@
+use std::fmt;
+use std::io;
+
+/// Maximum number of characters of the raw line kept in a [`ParseError`]
+const SNIPPET_LENGTH: usize = 200;
+
+/// The reason why a link queue event could not be parsed
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// The event is not valid JSON
+    InvalidJson(serde_json::Error),
+    /// A field of the event is absent or does not have the expected type
+    MissingField(&'static str),
+    /// A field of the event is present but does not have the expected shape
+    InvalidField {
+        field: &'static str,
+        source: serde_json::Error,
+    },
+    /// The type of the event is neither `push` nor `pop`
+    UnknownEventType(String),
+}
+
+impl ParseErrorKind {
+    /// A short description of the kind of error, used to group errors in reports
+    pub fn reason(&self) -> String {
+        match self {
+            Self::InvalidJson(_) => "invalid JSON".to_string(),
+            Self::MissingField(field) => format!("missing field `{field}`"),
+            Self::InvalidField { field, .. } => format!("invalid field `{field}`"),
+            Self::UnknownEventType(_) => "unknown event type".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidJson(source) => write!(f, "invalid JSON: {source}"),
+            Self::MissingField(field) => write!(f, "missing field `{field}`"),
+            Self::InvalidField { field, source } => write!(f, "invalid field `{field}`: {source}"),
+            Self::UnknownEventType(event_type) => write!(f, "unknown event type `{event_type}`"),
+        }
+    }
+}
+
+/// A line of the log that looks like a link queue event but could not be parsed
+#[derive(Debug)]
+pub struct ParseError {
+    /// The line number in the log, starting at 1
+    pub line: usize,
+    pub kind: ParseErrorKind,
+    /// The beginning of the raw line
+    pub snippet: String,
+}
+
+impl ParseError {
+    pub fn new(line: usize, kind: ParseErrorKind, raw_line: &str) -> Self {
+        Self {
+            line,
+            kind,
+            snippet: raw_line.chars().take(SNIPPET_LENGTH).collect(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {} in `{}`", self.line, self.kind, self.snippet)
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::InvalidJson(source) | ParseErrorKind::InvalidField { source, .. } => {
+                Some(source)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An error that interrupted the reading of a log
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Parse(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Parse(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<ParseError> for io::Error {
+    fn from(error: ParseError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::Io(error) => error,
+            Error::Parse(error) => error.into(),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{self, Write};
+
+use crate::query::query_id;
+use crate::residence::actor_name;
+use crate::run::Run;
+use crate::timeseries::CounterColumns;
+
+/// Write every link queue event as a flat CSV record, with one `push` and one `pop` column per
+/// producing actor found in the runs and a leading `run` column when a run is labelled.
+/// The events of a query are in log order and the queries are identified by [`query_id`].
+pub fn write_csv<W: Write>(runs: &[Run], writer: W) -> io::Result<()> {
+    let labelled = runs.iter().any(|run| run.label.is_some());
+    let counters = CounterColumns::new(runs);
+
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut header = Vec::new();
+    if labelled {
+        header.push("run".to_string());
+    }
+    header.extend(
+        [
+            "query_id",
+            "execution",
+            "type",
+            "url",
+            "parent",
+            "actor",
+            "timestamp",
+            "size",
+        ]
+        .map(String::from),
+    );
+    header.extend(counters.header());
+    writer.write_record(&header)?;
+
+    for run in runs {
+        for (key, query_history) in &run.history {
+            let id = query_id(&key.query);
+            for (event_type, event) in query_history.events() {
+                let mut record = Vec::new();
+                if labelled {
+                    record.push(run.label.clone().unwrap_or_default());
+                }
+                record.extend([
+                    id.clone(),
+                    key.execution.to_string(),
+                    event_type.as_str().to_string(),
+                    event.link.url.clone(),
+                    event.link.parent.clone().unwrap_or_default(),
+                    actor_name(event),
+                    event.link.timestamp.to_string(),
+                    event.queue.size.to_string(),
+                ]);
+                record.extend(counters.values(&event.queue.push, &event.queue.pop));
+                writer.write_record(&record)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_reader;
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":1.0,"producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/b","timestamp":2.0,"parent":"https://example.org/a"},"query":"ASK { ?s ?p ?o }","queue":{"size":1,"push":{"unknown":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://example.org/a","timestamp":3.0,"producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":0,"push":{"actor-a":1},"pop":{"actor-a":1}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn should_flatten_the_events() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+        let mut output = Vec::new();
+
+        write_csv(&[Run::new(None, history)], &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "query_id,execution,type,url,parent,actor,timestamp,size,push:actor-a,push:unknown,pop:actor-a,pop:unknown",
+                "dab3891081bc,1,push,https://example.org/a,,actor-a,1,1,1,0,0,0",
+                "dab3891081bc,1,pop,https://example.org/a,,actor-a,3,0,1,0,1,0",
+                "720ca56979ad,1,push,https://example.org/b,https://example.org/a,unknown,2,1,0,1,0,0",
+            ]
+        );
+    }
+}
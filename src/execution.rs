@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::DateTime;
+
+use crate::object::{LinkEvent, QueryEvent};
+
+/// Where the events of a query come from, the executions of different processes are never merged
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Source {
+    query: String,
+    hostname: Option<String>,
+    pid: Option<u64>,
+}
+
+/// State of an execution after its last event
+#[derive(Debug)]
+struct Execution {
+    number: usize,
+    /// Sum of the push and pop counters of the queue, which every event increments by one
+    counters: u64,
+    /// `link.timestamp` of the last event
+    timestamp: f64,
+    /// Bunyan `time` of the last event, in milliseconds since the Unix epoch
+    time: Option<i64>,
+}
+
+/// Tell apart the executions of a query whose events are interleaved in one log.
+///
+/// The events of different processes, identified by the Bunyan `hostname` and `pid`, belong to
+/// different executions. Within a process, an event continues the execution whose counters it
+/// increments, and starts a new execution when the counters restart, or when it comes more than
+/// the gap after the last event of the execution.
+#[derive(Debug, Default)]
+pub struct Executions {
+    gap: Option<Duration>,
+    sources: HashMap<Source, Vec<Execution>>,
+    count_by_query: HashMap<String, usize>,
+}
+
+impl Executions {
+    pub fn new(gap: Option<Duration>) -> Self {
+        Self {
+            gap,
+            ..Self::default()
+        }
+    }
+
+    /// Number of the execution of the query the event belongs to, starting at 1 for each query
+    pub fn number(&mut self, event: &QueryEvent) -> usize {
+        let source = Source {
+            query: event.query.clone(),
+            hostname: event.event.envelope.hostname.clone(),
+            pid: event.event.envelope.pid,
+        };
+        let counters = counters(&event.event);
+        let time = wall_clock(&event.event);
+        let executions = self.sources.entry(source).or_default();
+
+        // the execution with the most counters below those of the event is the one it continues,
+        // an event without counters continues the last execution
+        let continued = if counters == 0 {
+            executions.last_mut()
+        } else {
+            executions
+                .iter_mut()
+                .filter(|execution| execution.counters < counters)
+                .max_by_key(|execution| execution.counters)
+        };
+        let continued = continued.filter(|execution| match self.gap {
+            Some(gap) => elapsed(execution, event.event.link.timestamp, time) <= gap,
+            None => true,
+        });
+        match continued {
+            Some(execution) => {
+                execution.counters = counters;
+                execution.timestamp = event.event.link.timestamp;
+                execution.time = time;
+                execution.number
+            }
+            None => {
+                let count = self.count_by_query.entry(event.query.clone()).or_default();
+                *count += 1;
+                executions.push(Execution {
+                    number: *count,
+                    counters,
+                    timestamp: event.event.link.timestamp,
+                    time,
+                });
+                *count
+            }
+        }
+    }
+}
+
+fn counters(event: &LinkEvent) -> u64 {
+    event
+        .queue
+        .push
+        .values()
+        .chain(event.queue.pop.values())
+        .sum()
+}
+
+fn wall_clock(event: &LinkEvent) -> Option<i64> {
+    event
+        .envelope
+        .time
+        .as_deref()
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|time| time.timestamp_millis())
+}
+
+/// Time between the last event of the execution and an event, on the wall clock when both have
+/// a Bunyan `time` and otherwise in `link.timestamp`, which comunica gives in milliseconds
+fn elapsed(execution: &Execution, timestamp: f64, time: Option<i64>) -> Duration {
+    let milliseconds = match (execution.time, time) {
+        (Some(last), Some(time)) => (time - last) as f64,
+        _ => timestamp - execution.timestamp,
+    };
+    Duration::from_secs_f64(milliseconds.max(0.0) / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_line;
+
+    fn event(pid: u64, time: &str, size: u64, push: u64, pop: u64) -> QueryEvent {
+        let event_type = if pop > 0 { "pop" } else { "push" };
+        let line = format!(
+            r#"{{"pid":{pid},"data":{{"type":"{event_type}","link":{{"url":"https://example.org/","timestamp":1.0}},"query":"SELECT * WHERE {{ ?s ?p ?o }}","queue":{{"size":{size},"push":{{"actor-a":{push}}},"pop":{{"actor-a":{pop}}}}}}},"msg":"Link queue changed","time":"{time}"}}"#
+        );
+        parse_line(&line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn should_separate_interleaved_executions() {
+        let mut executions = Executions::default();
+        let events = [
+            event(1, "2024-07-05T12:00:00.000Z", 1, 1, 0),
+            event(1, "2024-07-05T12:00:00.001Z", 2, 2, 0),
+            // the counters restart, a second execution starts in the same process
+            event(1, "2024-07-05T12:00:00.002Z", 1, 1, 0),
+            event(1, "2024-07-05T12:00:00.003Z", 1, 2, 1),
+            event(1, "2024-07-05T12:00:00.004Z", 0, 1, 1),
+            // another process
+            event(2, "2024-07-05T12:00:00.005Z", 1, 1, 0),
+        ];
+
+        let numbers: Vec<usize> = events
+            .iter()
+            .map(|event| executions.number(event))
+            .collect();
+
+        assert_eq!(numbers, vec![1, 1, 2, 1, 2, 3]);
+    }
+
+    #[test]
+    fn should_start_an_execution_after_a_gap() {
+        let mut executions = Executions::new(Some(Duration::from_secs(60)));
+
+        let first = executions.number(&event(1, "2024-07-05T12:00:00.000Z", 1, 1, 0));
+        let close = executions.number(&event(1, "2024-07-05T12:00:30.000Z", 0, 1, 1));
+        let late = executions.number(&event(1, "2024-07-05T12:05:00.000Z", 1, 2, 1));
+
+        assert_eq!((first, close, late), (1, 1, 2));
+    }
+}
@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+
+use crate::object::{EventType, LinkEvent, QueryEvent};
+use crate::query::query_id;
+use crate::residence::actor_name;
+use crate::summary::host;
+
+/// A bound of a time window, either relative or on the wall clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeBound {
+    /// Milliseconds of `link.timestamp`
+    Timestamp(f64),
+    /// Wall-clock time compared with the `time` field of Bunyan
+    Time(DateTime<FixedOffset>),
+}
+
+impl TimeBound {
+    /// Position of the event relative to the bound, `None` when the event has no wall-clock time
+    fn compare(&self, event: &LinkEvent) -> Option<std::cmp::Ordering> {
+        match self {
+            Self::Timestamp(timestamp) => event.link.timestamp.partial_cmp(timestamp),
+            Self::Time(time) => event
+                .envelope
+                .time
+                .as_deref()
+                .and_then(|event_time| DateTime::parse_from_rfc3339(event_time).ok())
+                .map(|event_time| event_time.cmp(time)),
+        }
+    }
+}
+
+impl FromStr for TimeBound {
+    type Err = String;
+
+    /// Parse a number of milliseconds of `link.timestamp` or an RFC 3339 date
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(timestamp) = value.parse::<f64>() {
+            return Ok(Self::Timestamp(timestamp));
+        }
+        DateTime::parse_from_rfc3339(value)
+            .map(Self::Time)
+            .map_err(|_| format!("`{value}` is neither a `link.timestamp` nor an RFC 3339 date"))
+    }
+}
+
+/// The link queue events to keep, every criterion that is set must match
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Pattern of the one-line query text
+    pub query: Option<Regex>,
+    /// Ids of the queries, see [`query_id`]
+    pub query_ids: Vec<String>,
+    /// Pattern of the name of the actor that produced the link, `unknown` when comunica does not report it
+    pub actor: Option<Regex>,
+    /// Pattern of the url of the link
+    pub url: Option<Regex>,
+    /// Pattern of the host of the url of the link
+    pub host: Option<Regex>,
+    pub event_type: Option<EventType>,
+    /// Inclusive start of the time window
+    pub from: Option<TimeBound>,
+    /// Inclusive end of the time window
+    pub to: Option<TimeBound>,
+}
+
+impl Filter {
+    pub fn matches(&self, event: &QueryEvent) -> bool {
+        let link = &event.event.link;
+        self.query
+            .as_ref()
+            .is_none_or(|query| query.is_match(&event.query))
+            && (self.query_ids.is_empty() || self.query_ids.contains(&query_id(&event.query)))
+            && self
+                .actor
+                .as_ref()
+                .is_none_or(|actor| actor.is_match(&actor_name(&event.event)))
+            && self.url.as_ref().is_none_or(|url| url.is_match(&link.url))
+            && self
+                .host
+                .as_ref()
+                .is_none_or(|pattern| host(&link.url).is_some_and(|host| pattern.is_match(host)))
+            && self
+                .event_type
+                .is_none_or(|event_type| event_type == event.event_type)
+            && self.from.is_none_or(|from| {
+                from.compare(&event.event)
+                    .is_some_and(|order| order.is_ge())
+            })
+            && self
+                .to
+                .is_none_or(|to| to.compare(&event.event).is_some_and(|order| order.is_le()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_line;
+
+    fn event(event_type: &str, url: &str, actor: &str, timestamp: f64, time: &str) -> QueryEvent {
+        let line = format!(
+            r#"{{"data":{{"type":"{event_type}","link":{{"url":"{url}","timestamp":{timestamp},"producedByActor":{{"name":"{actor}"}}}},"query":"SELECT * WHERE {{ ?s ?p ?o }}","queue":{{"size":1,"push":{{}},"pop":{{}}}}}},"msg":"Link queue changed","time":"{time}"}}"#
+        );
+        parse_line(&line).unwrap().unwrap()
+    }
+
+    #[test]
+    fn should_parse_a_time_bound() {
+        assert_eq!("2.5".parse(), Ok(TimeBound::Timestamp(2.5)));
+        assert!(matches!(
+            "2024-07-05T12:06:08.654Z".parse(),
+            Ok(TimeBound::Time(_))
+        ));
+        assert!("yesterday".parse::<TimeBound>().is_err());
+    }
+
+    #[test]
+    fn should_keep_the_matching_events() {
+        let pushed = event(
+            "push",
+            "https://pod.example.org/profile",
+            "urn:comunica:default:extract-links/actors#predicates-solid",
+            10.0,
+            "2024-07-05T12:00:00.000Z",
+        );
+        let popped = event(
+            "pop",
+            "https://other.example.org/",
+            "urn:comunica:default:extract-links/actors#quad-pattern-query",
+            20.0,
+            "2024-07-05T12:01:00.000Z",
+        );
+
+        let by_actor = Filter {
+            actor: Some(Regex::new("predicates-solid$").unwrap()),
+            ..Filter::default()
+        };
+        let by_host = Filter {
+            host: Some(Regex::new(r"^other\.").unwrap()),
+            event_type: Some(EventType::Pop),
+            ..Filter::default()
+        };
+        let by_time = Filter {
+            from: Some("2024-07-05T12:00:30Z".parse().unwrap()),
+            ..Filter::default()
+        };
+        let by_timestamp = Filter {
+            to: Some(TimeBound::Timestamp(10.0)),
+            query_ids: vec![query_id("SELECT * WHERE { ?s ?p ?o }")],
+            ..Filter::default()
+        };
+
+        assert!(Filter::default().matches(&pushed));
+        assert_eq!(
+            (by_actor.matches(&pushed), by_actor.matches(&popped)),
+            (true, false)
+        );
+        assert_eq!(
+            (by_host.matches(&pushed), by_host.matches(&popped)),
+            (false, true)
+        );
+        assert_eq!(
+            (by_time.matches(&pushed), by_time.matches(&popped)),
+            (false, true)
+        );
+        assert_eq!(
+            (by_timestamp.matches(&pushed), by_timestamp.matches(&popped)),
+            (true, false)
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::input::Compression;
+
+/// Read the lines appended to a growing log, like `tail -F`.
+/// A truncated log is read again from its beginning and a rotated log is reopened once
+/// the remaining lines of the previous file are read. Compressed logs cannot be followed.
+pub struct Follower {
+    path: PathBuf,
+    reader: BufReader<File>,
+    position: u64,
+    /// The end of the log when its last line is not complete yet
+    partial: String,
+    id: Option<u64>,
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+impl Follower {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path)?;
+        let id = file_id(&file.metadata()?);
+        let mut reader = BufReader::new(file);
+        let compression = match Compression::from_magic_bytes(reader.fill_buf()?) {
+            Compression::None => Compression::from_extension(&path),
+            compression => compression,
+        };
+        if compression != Compression::None {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} is compressed, only a plain log can be followed",
+                    path.display()
+                ),
+            ));
+        }
+        Ok(Self {
+            path,
+            reader,
+            position: 0,
+            partial: String::new(),
+            id,
+        })
+    }
+
+    /// Call `on_line` with every complete line appended since the last call, returns the number of lines read
+    pub fn read_lines(
+        &mut self,
+        mut on_line: impl FnMut(&str) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        let mut count = 0;
+        loop {
+            if self.reader.get_ref().metadata()?.len() < self.position {
+                self.reader.seek(SeekFrom::Start(0))?;
+                self.position = 0;
+                self.partial.clear();
+            }
+            count += self.read_available(&mut on_line)?;
+            if !self.is_rotated()? {
+                return Ok(count);
+            }
+            // the last line of the previous file is complete, no more content is appended to it
+            if !self.partial.is_empty() {
+                on_line(&self.partial)?;
+                count += 1;
+            }
+            *self = Self::open(&self.path)?;
+        }
+    }
+
+    fn read_available(
+        &mut self,
+        on_line: &mut impl FnMut(&str) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        let mut count = 0;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self.reader.read_line(&mut line)?;
+            if read == 0 {
+                return Ok(count);
+            }
+            self.position += read as u64;
+            if !line.ends_with('\n') {
+                self.partial.push_str(&line);
+                continue;
+            }
+            let line = line.trim_end_matches(['\n', '\r']);
+            if self.partial.is_empty() {
+                on_line(line)?;
+            } else {
+                self.partial.push_str(line);
+                on_line(&self.partial)?;
+                self.partial.clear();
+            }
+            count += 1;
+        }
+    }
+
+    /// Whether another file was moved to the path of the log
+    fn is_rotated(&self) -> io::Result<bool> {
+        let id = match fs::metadata(&self.path) {
+            Ok(metadata) => file_id(&metadata),
+            // the log was moved and the new one is not created yet
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => return Err(error),
+        };
+        Ok(id.is_some() && id != self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn read(follower: &mut Follower) -> Vec<String> {
+        let mut lines = Vec::new();
+        follower
+            .read_lines(|line| {
+                lines.push(line.to_string());
+                Ok(())
+            })
+            .unwrap();
+        lines
+    }
+
+    fn append(path: &Path, content: &str) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn should_follow_a_growing_log() {
+        let directory =
+            std::env::temp_dir().join(format!("link-queue-follow-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("info");
+        fs::write(&path, "a\nb\n").unwrap();
+        let mut follower = Follower::open(&path).unwrap();
+
+        let initial = read(&mut follower);
+        append(&path, "c\nd");
+        let appended = read(&mut follower);
+        append(&path, "e\n");
+        let completed = read(&mut follower);
+        fs::write(&path, "f\n").unwrap();
+        let truncated = read(&mut follower);
+        append(&path, "g\n");
+        fs::rename(&path, directory.join("info.1")).unwrap();
+        fs::write(&path, "h\n").unwrap();
+        let rotated = read(&mut follower);
+        append(&path, "i");
+        assert!(read(&mut follower).is_empty());
+        fs::rename(&path, directory.join("info.2")).unwrap();
+        fs::write(&path, "j\n").unwrap();
+        let rotated_after_a_partial_line = read(&mut follower);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(initial, vec!["a", "b"]);
+        assert_eq!(appended, vec!["c"]);
+        assert_eq!(completed, vec!["de"]);
+        assert_eq!(truncated, vec!["f"]);
+        assert_eq!(rotated, vec!["g", "h"]);
+        assert_eq!(rotated_after_a_partial_line, vec!["i", "j"]);
+    }
+
+    #[test]
+    fn should_not_follow_a_compressed_log() {
+        let directory =
+            std::env::temp_dir().join(format!("link-queue-follow-gz-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let compressed = directory.join("info.zst");
+        fs::write(&compressed, zstd::encode_all(&b"a\n"[..], 0).unwrap()).unwrap();
+        let renamed = directory.join("info");
+        fs::copy(&compressed, &renamed).unwrap();
+        let empty = directory.join("info.gz");
+        fs::write(&empty, "").unwrap();
+
+        let errors: Vec<io::ErrorKind> = [&compressed, &renamed, &empty]
+            .into_iter()
+            .map(|path| Follower::open(path).err().unwrap().kind())
+            .collect();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(errors, vec![io::ErrorKind::InvalidInput; 3]);
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::object::{EventType, History, LinkEvent};
+use crate::residence::actor_name;
+use crate::run::Run;
+use crate::summary::{summarize, QuerySummary};
+
+/// Page of the report, the data of the runs replaces `{{data}}`
+const TEMPLATE: &str = include_str!("report.html");
+
+/// A link queue event as shown in the report
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ReportEvent<'a> {
+    timestamp: f64,
+    #[serde(rename = "type")]
+    event_type: EventType,
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent: Option<&'a str>,
+    actor: String,
+    size: u64,
+}
+
+impl<'a> ReportEvent<'a> {
+    fn new(event_type: EventType, event: &'a LinkEvent) -> Self {
+        Self {
+            timestamp: event.link.timestamp,
+            event_type,
+            url: &event.link.url,
+            parent: event.link.parent.as_deref(),
+            actor: actor_name(event),
+            size: event.queue.size,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct QueryReport<'a> {
+    name: String,
+    summary: QuerySummary,
+    events: Vec<ReportEvent<'a>>,
+}
+
+fn query_report(name: String, history: &History) -> QueryReport<'_> {
+    QueryReport {
+        name,
+        summary: summarize(history),
+        events: history
+            .events()
+            .into_iter()
+            .map(|(event_type, event)| ReportEvent::new(event_type, event))
+            .collect(),
+    }
+}
+
+/// Write a single HTML page, without external resources, charting the occupancy and the push and pop
+/// rates of every query with the counters of each actor and a searchable table of the events
+pub fn write_html<W: Write>(runs: &[Run], mut writer: W) -> io::Result<()> {
+    let queries: Vec<QueryReport> = runs
+        .iter()
+        .flat_map(|run| {
+            run.history
+                .iter()
+                .map(|(key, history)| query_report(run.name(key), history))
+        })
+        .collect();
+    // the data is embedded in a script element, which a `</script>` in a url would close
+    let data = serde_json::to_string(&queries)?.replace("</", "<\\/");
+    writer.write_all(TEMPLATE.replace("{{data}}", &data).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_reader;
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/</script>","timestamp":1.0,"producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://example.org/</script>","timestamp":2.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":0,"push":{"actor-a":1},"pop":{"actor-a":1}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn should_embed_the_events_of_each_query() {
+        let run = Run::new(None, parse_reader(LOG.as_bytes()).unwrap());
+        let mut output = Vec::new();
+
+        write_html(&[run], &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("{{data}}"));
+        assert!(!output.contains("https://example.org/</script>"));
+        assert!(!output.contains("src=\"http"));
+        let data = output
+            .split("<script id=\"data\" type=\"application/json\">")
+            .nth(1)
+            .and_then(|rest| rest.split("</script>").next())
+            .unwrap();
+        let queries: serde_json::Value = serde_json::from_str(data).unwrap();
+        assert_eq!(queries[0]["name"], "SELECT * WHERE { ?s ?p ?o }");
+        assert_eq!(queries[0]["summary"]["pops"], 1);
+        assert_eq!(
+            queries[0]["events"],
+            serde_json::json!([
+                {"timestamp": 1.0, "type": "push", "url": "https://example.org/</script>", "actor": "actor-a", "size": 1},
+                {"timestamp": 2.0, "type": "pop", "url": "https://example.org/</script>", "actor": "unknown", "size": 0},
+            ])
+        );
+    }
+}
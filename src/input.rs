@@ -0,0 +1,262 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+/// Path of the log read when no input is given and the standard input is a terminal
+pub const DEFAULT_INPUT: &str = "./info";
+
+/// Compression of a log, detected from its first bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    const GZIP_MAGIC: &'static [u8] = &[0x1f, 0x8b];
+    const ZSTD_MAGIC: &'static [u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+    const XZ_MAGIC: &'static [u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+    pub fn from_magic_bytes(bytes: &[u8]) -> Self {
+        if bytes.starts_with(Self::GZIP_MAGIC) {
+            Self::Gzip
+        } else if bytes.starts_with(Self::ZSTD_MAGIC) {
+            Self::Zstd
+        } else if bytes.starts_with(Self::XZ_MAGIC) {
+            Self::Xz
+        } else {
+            Self::None
+        }
+    }
+
+    pub fn from_extension(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => Self::Gzip,
+            Some("zst") => Self::Zstd,
+            Some("xz") => Self::Xz,
+            _ => Self::None,
+        }
+    }
+}
+
+/// Decompress `reader` on the fly. The compression is detected from the magic bytes
+/// and from the extension of `path` when the content is too short to hold them.
+pub fn decompress<R: BufRead + 'static>(
+    mut reader: R,
+    path: Option<&Path>,
+) -> io::Result<Box<dyn BufRead>> {
+    let head = reader.fill_buf()?;
+    let mut compression = Compression::from_magic_bytes(head);
+    if compression == Compression::None && head.len() < Compression::XZ_MAGIC.len() {
+        compression = path.map_or(Compression::None, Compression::from_extension);
+    }
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+    })
+}
+
+/// Where a comunica log is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    Path(PathBuf),
+}
+
+impl Input {
+    /// Resolve the input given on the command line, `-` is the standard input.
+    /// Without input the standard input is read when it is piped, [`DEFAULT_INPUT`] otherwise.
+    pub fn from_arg(arg: Option<&Path>) -> Self {
+        match arg {
+            Some(path) if path == Path::new("-") => Self::Stdin,
+            Some(path) => Self::Path(path.to_path_buf()),
+            None if !io::stdin().is_terminal() => Self::Stdin,
+            None => Self::Path(PathBuf::from(DEFAULT_INPUT)),
+        }
+    }
+
+    /// Resolve the inputs given on the command line. Each argument is resolved like [`Input::from_arg`],
+    /// except that the files of a directory are read in alphabetical order and that glob patterns are expanded.
+    pub fn resolve(args: &[PathBuf]) -> io::Result<Vec<Self>> {
+        if args.is_empty() {
+            return Ok(vec![Self::from_arg(None)]);
+        }
+        let mut inputs = Vec::new();
+        for arg in args {
+            if arg.is_dir() {
+                let mut files = fs::read_dir(arg)?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<io::Result<Vec<PathBuf>>>()?;
+                files.retain(|file| file.is_file());
+                files.sort();
+                inputs.extend(files.into_iter().map(Self::Path));
+            } else if let Some(pattern) = arg
+                .to_str()
+                .filter(|pattern| !arg.exists() && pattern.contains(['*', '?', '[']))
+            {
+                let paths = glob::glob(pattern)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?
+                    .collect::<Result<Vec<PathBuf>, _>>()
+                    .map_err(io::Error::from)?;
+                if paths.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("no log matches the pattern `{pattern}`"),
+                    ));
+                }
+                inputs.extend(paths.into_iter().map(Self::Path));
+            } else {
+                inputs.push(Self::from_arg(Some(arg)));
+            }
+        }
+        Ok(inputs)
+    }
+
+    /// Default identifier of the run read from this input, the file name of the log
+    pub fn label(&self) -> String {
+        match self {
+            Self::Stdin => "stdin".to_string(),
+            Self::Path(path) => path
+                .file_name()
+                .map_or(path.to_string_lossy(), |name| name.to_string_lossy())
+                .to_string(),
+        }
+    }
+
+    pub fn is_stdin(&self) -> bool {
+        matches!(self, Self::Stdin)
+    }
+
+    /// Open the log, decompressing it if it is compressed with gzip, zstd or xz
+    pub fn open(&self) -> io::Result<Box<dyn BufRead>> {
+        match self {
+            Self::Stdin => decompress(io::stdin().lock(), None),
+            Self::Path(path) => decompress(BufReader::new(File::open(path)?), Some(path)),
+        }
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdin => write!(f, "<stdin>"),
+            Self::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":1.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    fn read(compressed: Vec<u8>) -> String {
+        let mut content = String::new();
+        decompress(io::Cursor::new(compressed), None)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn should_read_an_uncompressed_log() {
+        assert_eq!(read(LOG.as_bytes().to_vec()), LOG);
+    }
+
+    #[test]
+    fn should_decompress_gzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(LOG.as_bytes()).unwrap();
+
+        assert_eq!(read(encoder.finish().unwrap()), LOG);
+    }
+
+    #[test]
+    fn should_decompress_zstd() {
+        let compressed = zstd::encode_all(LOG.as_bytes(), 0).unwrap();
+
+        assert_eq!(read(compressed), LOG);
+    }
+
+    #[test]
+    fn should_decompress_xz() {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 1);
+        encoder.write_all(LOG.as_bytes()).unwrap();
+
+        assert_eq!(read(encoder.finish().unwrap()), LOG);
+    }
+
+    #[test]
+    fn should_detect_the_compression_from_the_extension() {
+        assert_eq!(
+            Compression::from_extension(Path::new("info.gz")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("info.zst")),
+            Compression::Zstd
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("info.xz")),
+            Compression::Xz
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("info")),
+            Compression::None
+        );
+    }
+
+    #[test]
+    fn should_resolve_the_input() {
+        assert_eq!(Input::from_arg(Some(Path::new("-"))), Input::Stdin);
+        assert_eq!(
+            Input::from_arg(Some(Path::new("./log"))),
+            Input::Path(PathBuf::from("./log"))
+        );
+    }
+
+    #[test]
+    fn should_resolve_directories_and_patterns() {
+        let directory =
+            std::env::temp_dir().join(format!("link-queue-inputs-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        for name in ["b.log", "a.log", "c.txt"] {
+            fs::write(directory.join(name), LOG).unwrap();
+        }
+
+        let from_directory = Input::resolve(std::slice::from_ref(&directory)).unwrap();
+        let from_pattern = Input::resolve(&[directory.join("*.log")]).unwrap();
+        let no_match = Input::resolve(&[directory.join("*.gz")]);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(
+            from_directory
+                .iter()
+                .map(Input::label)
+                .collect::<Vec<String>>(),
+            vec!["a.log", "b.log", "c.txt"]
+        );
+        assert_eq!(
+            from_pattern,
+            vec![
+                Input::Path(directory.join("a.log")),
+                Input::Path(directory.join("b.log"))
+            ]
+        );
+        assert_eq!(no_match.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
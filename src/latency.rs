@@ -0,0 +1,298 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::DateTime;
+use serde::Serialize;
+
+use crate::object::{Envelope, History, SourceEvent, SourceEventType};
+use crate::residence::{pair, DurationStats};
+use crate::summary::host;
+
+/// The steps of the dereferencing of a popped link, at the Bunyan `time` of their records
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkTimeline {
+    pub url: String,
+    /// `None` when no push of the url precedes the pop
+    pub pushed: Option<String>,
+    pub popped: Option<String>,
+    /// `None` when the link was not requested after its pop
+    pub requested: Option<String>,
+    /// `None` when no source was identified after the request
+    pub identified: Option<String>,
+    /// Milliseconds between the push and the pop
+    pub waiting_time: Option<f64>,
+    /// Milliseconds between the pop and the request
+    pub request_delay: Option<f64>,
+    /// Milliseconds between the request and the identification of the source
+    pub latency: Option<f64>,
+}
+
+/// Latency of the links of a host
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HostLatency {
+    pub popped: usize,
+    pub requested: usize,
+    pub identified: usize,
+    pub request_delay: Option<DurationStats>,
+    pub latency: Option<DurationStats>,
+}
+
+/// Latency analysis of the links of a query
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyReport {
+    /// Timeline of every pop, in log order
+    pub links: Vec<LinkTimeline>,
+    /// Statistics by host of the url of the links, the links without a host are left out
+    pub latency_by_host: BTreeMap<String, HostLatency>,
+}
+
+/// Process and url of a source event, the source events of a process are only compared with the
+/// link queue events of the same process
+type SourceKey<'a> = (Option<&'a str>, Option<u64>, &'a str);
+
+/// The times of the requests and identifications of each url of a log, in milliseconds since the
+/// Unix epoch along with the Bunyan `time` they come from
+#[derive(Debug, Default)]
+struct Sources<'a> {
+    requests: HashMap<SourceKey<'a>, Vec<(i64, &'a str)>>,
+    identifications: HashMap<SourceKey<'a>, Vec<(i64, &'a str)>>,
+}
+
+impl<'a> Sources<'a> {
+    fn new(sources: &'a [SourceEvent]) -> Self {
+        let mut index = Self::default();
+        for source in sources {
+            let Some((time, text)) = wall_clock(&source.envelope) else {
+                continue;
+            };
+            let times = match source.event_type {
+                SourceEventType::Request => &mut index.requests,
+                SourceEventType::Identification => &mut index.identifications,
+            };
+            times
+                .entry(key(&source.envelope, &source.url))
+                .or_default()
+                .push((time, text));
+        }
+        for times in index
+            .requests
+            .values_mut()
+            .chain(index.identifications.values_mut())
+        {
+            times.sort_by_key(|(time, _)| *time);
+        }
+        index
+    }
+}
+
+/// The next time at or after `after` of a url that was not taken yet, `taken` counts the times of
+/// each url already taken or skipped
+fn next_time<'a>(
+    times: &HashMap<SourceKey<'a>, Vec<(i64, &'a str)>>,
+    taken: &mut HashMap<SourceKey<'a>, usize>,
+    key: SourceKey<'a>,
+    after: i64,
+) -> Option<(i64, &'a str)> {
+    let times = times.get(&key)?;
+    let position = taken.entry(key).or_default();
+    while times.get(*position).is_some_and(|(time, _)| *time < after) {
+        *position += 1;
+    }
+    let time = times.get(*position).copied();
+    if time.is_some() {
+        *position += 1;
+    }
+    time
+}
+
+/// Follow every pop of a query to the first request of its url after the pop, and the request to
+/// the first identification of the url as a source after it. The pops are paired with their push
+/// like in [`residence`](crate::residence::residence)
+pub fn latency(history: &History, sources: &[SourceEvent]) -> LatencyReport {
+    let sources = Sources::new(sources);
+    let mut requested: HashMap<SourceKey, usize> = HashMap::new();
+    let mut identified: HashMap<SourceKey, usize> = HashMap::new();
+
+    let mut links = Vec::new();
+    for (push, pop) in pair(history).pops {
+        let popped = wall_clock(&pop.envelope);
+        let key = key(&pop.envelope, &pop.link.url);
+        let request = popped
+            .and_then(|(popped, _)| next_time(&sources.requests, &mut requested, key, popped));
+        let identification = request.and_then(|(request, _)| {
+            next_time(&sources.identifications, &mut identified, key, request)
+        });
+        let pushed = push.and_then(|push| wall_clock(&push.envelope));
+
+        links.push(LinkTimeline {
+            url: pop.link.url.clone(),
+            pushed: pushed.map(|(_, text)| text.to_string()),
+            popped: popped.map(|(_, text)| text.to_string()),
+            requested: request.map(|(_, text)| text.to_string()),
+            identified: identification.map(|(_, text)| text.to_string()),
+            waiting_time: elapsed(pushed, popped),
+            request_delay: elapsed(popped, request),
+            latency: elapsed(request, identification),
+        });
+    }
+
+    let mut by_host: BTreeMap<&str, Vec<&LinkTimeline>> = BTreeMap::new();
+    for link in &links {
+        if let Some(host) = host(&link.url) {
+            by_host.entry(host).or_default().push(link);
+        }
+    }
+    let latency_by_host = by_host
+        .into_iter()
+        .map(|(host, links)| {
+            let stats = |value: fn(&LinkTimeline) -> Option<f64>| {
+                DurationStats::from_durations(links.iter().filter_map(|link| value(link)).collect())
+            };
+            let latency = HostLatency {
+                popped: links.len(),
+                requested: links.iter().filter(|link| link.requested.is_some()).count(),
+                identified: links
+                    .iter()
+                    .filter(|link| link.identified.is_some())
+                    .count(),
+                request_delay: stats(|link| link.request_delay),
+                latency: stats(|link| link.latency),
+            };
+            (host.to_string(), latency)
+        })
+        .collect();
+
+    LatencyReport {
+        links,
+        latency_by_host,
+    }
+}
+
+/// The process of a record and a url without its fragment, which is not part of the request
+fn key<'a>(envelope: &'a Envelope, url: &'a str) -> SourceKey<'a> {
+    let url = url.split_once('#').map_or(url, |(url, _)| url);
+    (envelope.hostname.as_deref(), envelope.pid, url)
+}
+
+/// The Bunyan `time` of a record in milliseconds since the Unix epoch, along with its text
+fn wall_clock(envelope: &Envelope) -> Option<(i64, &str)> {
+    let text = envelope.time.as_deref()?;
+    let time = DateTime::parse_from_rfc3339(text).ok()?;
+    Some((time.timestamp_millis(), text))
+}
+
+fn elapsed(from: Option<(i64, &str)>, to: Option<(i64, &str)>) -> Option<f64> {
+    Some((to?.0 - from?.0) as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::HistoryKey;
+    use crate::parser::{parse_reader, LinkQueueParser};
+
+    fn event(url: &str, event_type: &str, time: &str) -> String {
+        format!(
+            r#"{{"hostname":"h","pid":1,"level":10,"data":{{"type":"{event_type}","link":{{"url":"{url}","timestamp":1.0}},"query":"SELECT * WHERE {{ ?s ?p ?o }}","queue":{{"size":1,"push":{{}},"pop":{{}}}}}},"msg":"Link queue changed","time":"{time}"}}"#
+        )
+    }
+
+    fn source(message: &str, time: &str) -> String {
+        format!(
+            r#"{{"hostname":"h","pid":1,"level":30,"actor":"urn:comunica:default:http/actors#fetch","msg":"{message}","time":"{time}"}}"#
+        )
+    }
+
+    #[test]
+    fn should_not_pair_a_pop_with_a_later_push() {
+        let log = [
+            event("https://a.example.org/", "pop", "2024-07-05T12:00:00.000Z"),
+            event("https://a.example.org/", "push", "2024-07-05T12:00:00.010Z"),
+        ]
+        .join("\n");
+        let history = parse_reader(log.as_bytes()).unwrap();
+
+        let report = latency(history.values().next().unwrap(), &[]);
+
+        assert_eq!(report.links[0].pushed, None);
+        assert_eq!(report.links[0].waiting_time, None);
+    }
+
+    #[test]
+    fn should_follow_the_popped_links() {
+        let log = [
+            event("https://a.example.org/", "push", "2024-07-05T12:00:00.000Z"),
+            event(
+                "https://b.example.org/#me",
+                "push",
+                "2024-07-05T12:00:00.010Z",
+            ),
+            // a request of the url before its pop does not belong to the pop
+            source(
+                "Requesting https://a.example.org/",
+                "2024-07-05T12:00:00.015Z",
+            ),
+            event("https://a.example.org/", "pop", "2024-07-05T12:00:00.020Z"),
+            source(
+                "Requesting https://a.example.org/",
+                "2024-07-05T12:00:00.025Z",
+            ),
+            event(
+                "https://b.example.org/#me",
+                "pop",
+                "2024-07-05T12:00:00.030Z",
+            ),
+            source(
+                "Requesting https://b.example.org/",
+                "2024-07-05T12:00:00.040Z",
+            ),
+            source(
+                "Identified as file source: https://a.example.org/",
+                "2024-07-05T12:00:00.125Z",
+            ),
+        ]
+        .join("\n");
+        let mut parser = LinkQueueParser::new().with_sources();
+        parser.read(log.as_bytes()).unwrap();
+        let sources = parser.take_sources();
+        assert_eq!(sources.len(), 4);
+
+        let report = latency(
+            &parser.history()[&HistoryKey::new("SELECT * WHERE { ?s ?p ?o }")],
+            &sources,
+        );
+
+        assert_eq!(
+            report.links[0],
+            LinkTimeline {
+                url: "https://a.example.org/".to_string(),
+                pushed: Some("2024-07-05T12:00:00.000Z".to_string()),
+                popped: Some("2024-07-05T12:00:00.020Z".to_string()),
+                requested: Some("2024-07-05T12:00:00.025Z".to_string()),
+                identified: Some("2024-07-05T12:00:00.125Z".to_string()),
+                waiting_time: Some(20.0),
+                request_delay: Some(5.0),
+                latency: Some(100.0),
+            }
+        );
+        let unidentified = &report.links[1];
+        assert_eq!(
+            (unidentified.request_delay, unidentified.latency),
+            (Some(10.0), None)
+        );
+
+        let host = &report.latency_by_host["b.example.org"];
+        assert_eq!((host.popped, host.requested, host.identified), (1, 1, 0));
+        assert_eq!(host.latency, None);
+        assert_eq!(
+            report.latency_by_host["a.example.org"]
+                .latency
+                .as_ref()
+                .unwrap()
+                .max,
+            100.0
+        );
+    }
+}
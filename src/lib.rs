@@ -0,0 +1,31 @@
+//! Parse the occupancy of the link queue of the
+//! [comunica link traversal query engine](https://github.com/comunica/comunica-feature-link-traversal).
+pub mod dashboard;
+pub mod error;
+pub mod events;
+pub mod execution;
+pub mod filter;
+pub mod follow;
+pub mod html;
+pub mod input;
+pub mod latency;
+pub mod object;
+pub mod parser;
+pub mod plot;
+pub mod pretty;
+pub mod query;
+pub mod residence;
+pub mod run;
+pub mod sqlite;
+pub mod summary;
+pub mod timeseries;
+pub mod tree;
+pub mod util;
+
+pub use error::{Error, ParseError, ParseErrorKind};
+pub use object::{
+    Envelope, EventType, History, HistoryKey, Link, LinkEvent, ProducedByActor, QueryEvent, Queue,
+    SourceEvent, SourceEventType,
+};
+pub use parser::{parse_file, parse_reader, Diagnostics, LinkQueueParser, LogFormat, Mode};
+pub use run::Run;
@@ -1,33 +1,639 @@
-use clap::Parser;
-use cli::Cli;
-use object::History;
-use std::collections::HashMap;
+use clap::{CommandFactory, Parser};
+use cli::{
+    Cli, Command, FilterArgs, InputFormat, PlotAxis, QueueOperation, SummaryFormat,
+    TimeseriesFormat, TreeFormat,
+};
+use comunica_link_queue_parser_rs::dashboard;
+use comunica_link_queue_parser_rs::events;
+use comunica_link_queue_parser_rs::filter::Filter;
+use comunica_link_queue_parser_rs::follow::Follower;
+use comunica_link_queue_parser_rs::html;
+use comunica_link_queue_parser_rs::input::Input;
+use comunica_link_queue_parser_rs::latency;
+use comunica_link_queue_parser_rs::plot::{self, PlotOptions, TimeAxis};
+use comunica_link_queue_parser_rs::query::{self, query_id, query_table, QueryEntry, QueryNames};
+use comunica_link_queue_parser_rs::residence;
+use comunica_link_queue_parser_rs::run::{Run, RunEvent};
+use comunica_link_queue_parser_rs::sqlite;
+use comunica_link_queue_parser_rs::summary::{self, QuerySummary};
+use comunica_link_queue_parser_rs::tree::{self, TraversalTree};
+use comunica_link_queue_parser_rs::{
+    timeseries, Diagnostics, Error, EventType, History, LinkQueueParser, LogFormat, Mode,
+    ParseError, QueryEvent,
+};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::HashSet;
 use std::fs::{self, File};
-use std::io::{self, prelude::*, BufReader};
-use std::path::PathBuf;
+use std::io::{self, prelude::*, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::slice;
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+use std::time::Duration;
 mod cli;
-mod object;
-mod util;
+
+/// Outcome of the parsing of an input
+struct Report {
+    label: String,
+    diagnostics: Diagnostics,
+}
+
+impl Report {
+    fn new(input: &Input, parser: &LinkQueueParser) -> Self {
+        Self {
+            label: input.label(),
+            diagnostics: parser.diagnostics(),
+        }
+    }
+}
+
+/// Stop at the first malformed link queue event of a log, with `--fail-on-error`
+fn fail(label: &str, parser: &LinkQueueParser, error: &ParseError) -> ! {
+    eprintln!("{}", parser.diagnostics());
+    eprintln!("first malformed link queue event of {label} at {error}");
+    process::exit(1);
+}
 
 fn main() -> io::Result<()> {
     let cli = Cli::parse();
-    let input_file = cli.input.unwrap_or(PathBuf::from("./info"));
-    let output_file = cli.output.unwrap_or(PathBuf::from("./occupancy.json"));
+    let inputs = Input::resolve(&cli.input)?;
+    if !cli.label.is_empty() && cli.label.len() != inputs.len() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::WrongNumberOfValues,
+                format!(
+                    "{} label(s) were given for {} input(s)",
+                    cli.label.len(),
+                    inputs.len()
+                ),
+            )
+            .exit();
+    }
+    let labels = run_labels(&inputs, &cli.label);
+    let names = match &cli.query_names {
+        Some(path) => QueryNames::load(path)?,
+        None => QueryNames::default(),
+    };
+    let output_file = cli
+        .output
+        .clone()
+        .or(default_output_file(cli.command.as_ref()).map(PathBuf::from));
+    let mut reports = Vec::new();
+    if cli.html.is_some() && matches!(cli.command, Some(Command::Stream | Command::Dashboard)) {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "the HTML report is built from the history, which the stream and dashboard subcommands do not keep",
+            )
+            .exit();
+    }
+
+    if let Some(Command::Dashboard) = cli.command {
+        let [input] = inputs.as_slice() else {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "the dashboard reads a single log",
+                )
+                .exit();
+        };
+        let (sender, receiver) = mpsc::sync_channel(dashboard::LINE_BUFFER);
+        let input = input.clone();
+        let follow = cli.follow;
+        let refresh = Duration::from_millis(cli.refresh);
+        thread::spawn(move || {
+            if let Err(error) = send_lines(&input, follow, refresh, &sender) {
+                let _ = sender.send(Err(error));
+            }
+        });
+        return dashboard::run(parser(&cli), receiver, refresh);
+    }
+
+    if cli.follow {
+        if let Some(Command::Sqlite) = cli.command {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "the runs are appended to the database, so it cannot be rewritten in follow mode",
+                )
+                .exit();
+        }
+        let [Input::Path(path)] = inputs.as_slice() else {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "the follow mode reads a single log file",
+                )
+                .exit();
+        };
+        let label = labels.into_iter().next().flatten();
+        return follow(&cli, path, label, output_file, &names);
+    }
+
+    if let Some(Command::Stream) = cli.command {
+        let output_file = output_file.expect("the stream command has a default output");
+        let mut output = None;
+        for (input, label) in inputs.iter().zip(&labels) {
+            if cli.per_input || output.is_none() {
+                let output_file = if cli.per_input {
+                    labelled_path(&output_file, &input.label())
+                } else {
+                    output_file.clone()
+                };
+                output = Some(BufWriter::new(File::create(output_file)?));
+            }
+            let output = output.as_mut().expect("the output is opened above");
+            let label = if cli.per_input {
+                None
+            } else {
+                label.as_deref()
+            };
+            let mut parser = parser(&cli);
+            match stream(&mut parser, input, label, output, cli.print) {
+                Err(Error::Parse(error)) => fail(&input.label(), &parser, &error),
+                result => result?,
+            }
+            reports.push(Report::new(input, &parser));
+        }
+    } else {
+        let mut runs = Vec::new();
+        for (input, label) in inputs.iter().zip(labels) {
+            let mut parser = parser(&cli);
+            match parser.read(input.open()?) {
+                Err(Error::Parse(error)) => fail(&input.label(), &parser, &error),
+                result => result?,
+            }
+            reports.push(Report::new(input, &parser));
+            let sources = parser.take_sources();
+            runs.push(Run {
+                label,
+                history: parser.into_history(),
+                sources,
+            });
+        }
+        if let Some(html) = &cli.html {
+            html::write_html(&runs, BufWriter::new(File::create(html)?))?;
+        }
+
+        if let Some(Command::Sqlite) = cli.command {
+            // the runs are kept apart in the database, so there is one output even with --per-input
+            let output_file = output_file.expect("the sqlite command has a default output");
+            // runs accumulate in the database, so they are labelled even when a single log is parsed
+            let runs: Vec<Run> = runs
+                .into_iter()
+                .zip(&reports)
+                .map(|(run, report)| {
+                    Run::new(run.label.or(Some(report.label.clone())), run.history)
+                })
+                .collect();
+            sqlite::write_sqlite(&runs, output_file)?;
+        } else if cli.per_input {
+            for (run, report) in runs.into_iter().zip(&reports) {
+                let run = Run { label: None, ..run };
+                let output = render(cli.command.as_ref(), slice::from_ref(&run), &names)?;
+                let output_file = output_file
+                    .as_ref()
+                    .map(|output_file| labelled_path(output_file, &report.label));
+                write_output(output, output_file, cli.print)?;
+            }
+        } else {
+            let output = render(cli.command.as_ref(), &runs, &names)?;
+            write_output(output, output_file, cli.print)?;
+        }
+    }
+
+    for report in &reports {
+        if reports.len() > 1 {
+            eprintln!("{}:", report.label);
+        }
+        eprintln!("{}", report.diagnostics);
+    }
+
+    Ok(())
+}
+
+/// The label of the run of each input, runs are only labelled when there are several inputs
+/// or when labels are given
+fn run_labels(inputs: &[Input], labels: &[String]) -> Vec<Option<String>> {
+    if !labels.is_empty() {
+        return labels.iter().cloned().map(Some).collect();
+    }
+    if inputs.len() == 1 {
+        return vec![None];
+    }
+    let mut seen = HashSet::new();
+    inputs
+        .iter()
+        .map(|input| {
+            let label = input.label();
+            // logs with the same file name in different directories are identified by their path
+            if seen.insert(label.clone()) {
+                Some(label)
+            } else {
+                Some(input.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Insert the label of a run before the extension of an output file
+fn labelled_path(path: &Path, label: &str) -> PathBuf {
+    let label: String = label
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{stem}.{label}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{label}"),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Write each link queue event of `input` as a line of JSON as soon as it is read
+fn stream<W: Write>(
+    parser: &mut LinkQueueParser,
+    input: &Input,
+    label: Option<&str>,
+    output: &mut W,
+    print: bool,
+) -> Result<(), Error> {
+    for event in parser.events(input.open()?) {
+        write_event(&event?, label, output, print)?;
+        // a piped log is read while the query is running, so the events are made visible right away
+        if input.is_stdin() {
+            output.flush()?;
+        }
+    }
+    Ok(output.flush()?)
+}
+
+/// Write an event as a line of JSON to `output`, and to the standard output if asked
+fn write_event<W: Write>(
+    event: &QueryEvent,
+    label: Option<&str>,
+    output: &mut W,
+    print: bool,
+) -> io::Result<()> {
+    let mut line = serde_json::to_vec(&RunEvent::new(label, event))?;
+    line.push(b'\n');
+    output.write_all(&line)?;
+    if print {
+        io::stdout().write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// Parse the lines appended to the log at `path` until the process is interrupted,
+/// the output is rewritten and the progress is reported after each refresh with new lines
+fn follow(
+    cli: &Cli,
+    path: &Path,
+    label: Option<String>,
+    output_file: Option<PathBuf>,
+    names: &QueryNames,
+) -> io::Result<()> {
+    let mut follower = match Follower::open(path) {
+        // a compressed log
+        Err(error) if error.kind() == io::ErrorKind::InvalidInput => Cli::command()
+            .error(clap::error::ErrorKind::InvalidValue, error)
+            .exit(),
+        result => result?,
+    };
+    let mut parser = parser(cli);
+    let mut run = Run::new(label, IndexMap::new());
+    let mut stream_output = match (&cli.command, &output_file) {
+        (Some(Command::Stream), Some(output_file)) => {
+            Some(BufWriter::new(File::create(output_file)?))
+        }
+        _ => None,
+    };
+
+    // the first malformed event with --fail-on-error, the lines after it are not parsed
+    let mut first_error = None;
+    loop {
+        let read = follower.read_lines(|line| {
+            if first_error.is_some() {
+                return Ok(());
+            }
+            if let Err(error) = parser.queue_line(line) {
+                first_error = Some(error);
+            }
+            while let Some(event) = parser.next_event() {
+                match stream_output.as_mut() {
+                    Some(output) => write_event(&event, run.label.as_deref(), output, cli.print)?,
+                    None => run
+                        .history
+                        .entry(event.key())
+                        .or_default()
+                        .add(event.event_type, event.event),
+                }
+            }
+            Ok(())
+        })?;
+
+        if read > 0 {
+            run.sources.append(&mut parser.take_sources());
+            match stream_output.as_mut() {
+                Some(output) => output.flush()?,
+                None => {
+                    write_output(
+                        render(cli.command.as_ref(), slice::from_ref(&run), names)?,
+                        output_file.clone(),
+                        cli.print,
+                    )?;
+                    if let Some(html) = &cli.html {
+                        html::write_html(
+                            slice::from_ref(&run),
+                            BufWriter::new(File::create(html)?),
+                        )?;
+                    }
+                }
+            }
+            let diagnostics = parser.diagnostics();
+            eprintln!(
+                "lines read: {}, parsed events: {}, skipped lines: {}",
+                diagnostics.lines,
+                diagnostics.parsed_events,
+                diagnostics.skipped_line_count()
+            );
+        }
+        if let Some(error) = &first_error {
+            fail(&path.display().to_string(), &parser, error);
+        }
+        thread::sleep(Duration::from_millis(cli.refresh));
+    }
+}
 
-    let file = File::open(input_file)?;
-    let reader = BufReader::new(file);
-    let mut history: HashMap<String, History> = HashMap::new();
-    for line in reader.lines() {
-        let _ = util::process_line(&line?, &mut history);
+/// Send the lines of `input` to the dashboard, a followed log is read until the dashboard is closed
+fn send_lines(
+    input: &Input,
+    follow: bool,
+    refresh: Duration,
+    lines: &SyncSender<io::Result<String>>,
+) -> io::Result<()> {
+    let disconnected = |_| io::Error::from(io::ErrorKind::BrokenPipe);
+    match input {
+        Input::Path(path) if follow => {
+            let mut follower = Follower::open(path)?;
+            loop {
+                follower
+                    .read_lines(|line| lines.send(Ok(line.to_string())).map_err(disconnected))?;
+                thread::sleep(refresh);
+            }
+        }
+        _ => {
+            for line in input.open()?.lines() {
+                lines.send(Ok(line?)).map_err(disconnected)?;
+            }
+            Ok(())
+        }
     }
+}
 
-    let json_string = serde_json::to_string(&history)?;
+/// Write the output to `output_file`, the output is printed if asked or if there is no file
+fn write_output(output: Vec<u8>, output_file: Option<PathBuf>, print: bool) -> io::Result<()> {
+    if print || output_file.is_none() {
+        println!("{}", String::from_utf8_lossy(&output));
+    }
 
-    if cli.print {
-        println!("{json_string}");
+    if let Some(output_file) = output_file {
+        fs::write(output_file, output).expect("Unable to write file");
     }
+    Ok(())
+}
 
-    fs::write(output_file, json_string).expect("Unable to write file");
+/// The default output file of `command`,
+/// the output of a command without default output file is printed unless `--output` is given
+fn default_output_file(command: Option<&Command>) -> Option<&'static str> {
+    match command {
+        None => Some("./occupancy.json"),
+        Some(Command::Stream) => Some("./occupancy.ndjson"),
+        Some(Command::Timeseries { format }) => match format {
+            TimeseriesFormat::Csv => Some("./timeseries.csv"),
+            TimeseriesFormat::Ndjson => Some("./timeseries.ndjson"),
+        },
+        Some(Command::Events) => Some("./events.csv"),
+        Some(Command::Queries) => Some("./queries.csv"),
+        Some(Command::Sqlite) => Some("./link-queue.sqlite"),
+        Some(Command::Residence) => Some("./residence.json"),
+        Some(Command::Latency) => Some("./latency.json"),
+        Some(Command::Tree { format, .. }) => match format {
+            TreeFormat::Json => Some("./tree.json"),
+            TreeFormat::Dot => Some("./tree.dot"),
+            TreeFormat::Graphml => Some("./tree.graphml"),
+        },
+        Some(Command::Plot { .. }) => Some("./plot.svg"),
+        Some(Command::Summary { .. }) | Some(Command::Dashboard) => None,
+    }
+}
 
+/// A parser reading the log format, telling apart the executions of a query and filtering the events as configured
+/// on the command line
+fn parser(cli: &Cli) -> LinkQueueParser {
+    let format = match cli.log_format {
+        InputFormat::Auto => LogFormat::Auto,
+        InputFormat::Bunyan => LogFormat::Bunyan,
+        InputFormat::Pretty => LogFormat::Pretty,
+    };
+    let mode = if cli.fail_on_error {
+        Mode::Strict
+    } else {
+        Mode::Lenient
+    };
+    let mut parser = LinkQueueParser::with_mode(mode)
+        .with_format(format)
+        .with_filter(filter(&cli.filter));
+    if let Some(Command::Latency) = cli.command {
+        parser = parser.with_sources();
+    }
+    match cli.execution_gap {
+        Some(gap) => parser.with_execution_gap(Duration::from_millis(gap)),
+        None => parser,
+    }
+}
+
+fn filter(args: &FilterArgs) -> Filter {
+    Filter {
+        query: args.query.clone(),
+        query_ids: args.query_id.clone(),
+        actor: args.actor.clone(),
+        url: args.url.clone(),
+        host: args.host.clone(),
+        event_type: args.event_type.map(|operation| match operation {
+            QueueOperation::Push => EventType::Push,
+            QueueOperation::Pop => EventType::Pop,
+        }),
+        from: args.from,
+        to: args.to,
+    }
+}
+
+/// Output of a command keyed by query id, along with the table of the queries
+#[derive(Serialize)]
+struct KeyedOutput<T> {
+    queries: IndexMap<String, QueryEntry>,
+    results: T,
+}
+
+/// Output of a command for one execution of a query
+#[derive(Serialize)]
+struct ExecutionOutput<T> {
+    execution: usize,
+    #[serde(flatten)]
+    result: T,
+}
+
+/// Serialize `by_query` applied to each execution of a query, the executions of each run are listed
+/// by query id and keyed by the label of the runs when they are labelled
+fn write_json<'a, T: Serialize>(
+    runs: &'a [Run],
+    names: &QueryNames,
+    by_query: impl Fn(&'a Run, &'a History) -> T,
+    output: &mut Vec<u8>,
+) -> io::Result<()> {
+    let run_by_query = |run: &'a Run| -> IndexMap<String, Vec<ExecutionOutput<T>>> {
+        let mut executions: IndexMap<String, Vec<ExecutionOutput<T>>> = IndexMap::new();
+        for (key, history) in &run.history {
+            executions
+                .entry(query_id(&key.query))
+                .or_default()
+                .push(ExecutionOutput {
+                    execution: key.execution,
+                    result: by_query(run, history),
+                });
+        }
+        executions
+    };
+    let queries = query_table(queries(runs), names);
+    match runs {
+        [run @ Run { label: None, .. }] => serde_json::to_writer(
+            output,
+            &KeyedOutput {
+                queries,
+                results: run_by_query(run),
+            },
+        )?,
+        _ => {
+            let by_run: IndexMap<&str, IndexMap<String, Vec<ExecutionOutput<T>>>> = runs
+                .iter()
+                .map(|run| (run.label.as_deref().unwrap_or_default(), run_by_query(run)))
+                .collect();
+            serde_json::to_writer(
+                output,
+                &KeyedOutput {
+                    queries,
+                    results: by_run,
+                },
+            )?
+        }
+    }
     Ok(())
 }
+
+/// The text of the queries of the runs, once per execution
+fn queries(runs: &[Run]) -> impl Iterator<Item = &String> {
+    runs.iter()
+        .flat_map(|run| run.history.keys().map(|key| &key.query))
+}
+
+/// Render the output of `command` for every run
+fn render(command: Option<&Command>, runs: &[Run], names: &QueryNames) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    match command {
+        Some(Command::Stream) => unreachable!("streamed events are written as they are read"),
+        Some(Command::Dashboard) => unreachable!("the dashboard is drawn in the terminal"),
+        Some(Command::Sqlite) => unreachable!("the runs are appended to the database"),
+        None => write_json(runs, names, |_, history| history, &mut output)?,
+        Some(Command::Timeseries { format }) => match format {
+            TimeseriesFormat::Csv => timeseries::write_csv(runs, &mut output)?,
+            TimeseriesFormat::Ndjson => timeseries::write_ndjson(runs, &mut output)?,
+        },
+        Some(Command::Events) => events::write_csv(runs, &mut output)?,
+        Some(Command::Queries) => {
+            query::write_csv(&query_table(queries(runs), names), &mut output)?
+        }
+        Some(Command::Residence) => write_json(
+            runs,
+            names,
+            |_, history| residence::residence(history),
+            &mut output,
+        )?,
+        Some(Command::Latency) => write_json(
+            runs,
+            names,
+            |run, history| latency::latency(history, &run.sources),
+            &mut output,
+        )?,
+        Some(Command::Tree { format, chains }) => {
+            let trees: Vec<(String, TraversalTree)> = runs
+                .iter()
+                .flat_map(|run| {
+                    run.history
+                        .iter()
+                        .map(|(key, history)| (run.name(key), TraversalTree::from_history(history)))
+                })
+                .collect();
+            match format {
+                TreeFormat::Json => write_json(
+                    runs,
+                    names,
+                    |_, history| TraversalTree::from_history(history).summary(*chains),
+                    &mut output,
+                )?,
+                TreeFormat::Dot => {
+                    tree::write_dot(trees.iter().map(|(name, tree)| (name, tree)), &mut output)?
+                }
+                TreeFormat::Graphml => {
+                    tree::write_graphml(trees.iter().map(|(name, tree)| (name, tree)), &mut output)?
+                }
+            }
+        }
+        Some(Command::Plot {
+            axis,
+            actors,
+            overlay,
+        }) => {
+            let options = PlotOptions {
+                axis: match axis {
+                    PlotAxis::Relative => TimeAxis::Relative,
+                    PlotAxis::WallClock => TimeAxis::WallClock,
+                },
+                actors: *actors,
+                overlay: *overlay,
+            };
+            plot::write_svg(runs, &options, &mut output)?
+        }
+        Some(Command::Summary { format }) => match format {
+            SummaryFormat::Table => {
+                let summaries: Vec<(String, QuerySummary)> = runs
+                    .iter()
+                    .flat_map(|run| {
+                        run.history
+                            .iter()
+                            .map(|(key, history)| (run.name(key), summary::summarize(history)))
+                    })
+                    .collect();
+                summary::write_table(
+                    summaries.iter().map(|(name, summary)| (name, summary)),
+                    &mut output,
+                )?
+            }
+            SummaryFormat::Json => write_json(
+                runs,
+                names,
+                |_, history| summary::summarize(history),
+                &mut output,
+            )?,
+        },
+    }
+    Ok(output)
+}
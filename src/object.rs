@@ -1,14 +1,294 @@
-use serde::Serialize;
-use serde_json::{self, Map};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::vec::Vec;
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Default, Serialize, PartialEq)]
 pub struct History {
     pub push: Vec<LinkEvent>,
     pub pop: Vec<LinkEvent>,
+    /// Type of each event in log order, to interleave the pushes and pops as they were logged
+    #[serde(skip)]
+    pub order: Vec<EventType>,
 }
-#[derive(Debug, Serialize, PartialEq)]
+
+impl History {
+    pub fn add(&mut self, event_type: EventType, event: LinkEvent) {
+        match event_type {
+            EventType::Push => self.push.push(event),
+            EventType::Pop => self.pop.push(event),
+        }
+        self.order.push(event_type);
+    }
+
+    /// The pushes and pops in log order. When the order is unknown, because the history was not
+    /// built with [`History::add`], they are ordered by `link.timestamp`, pushes first.
+    pub fn events(&self) -> Vec<(EventType, &LinkEvent)> {
+        if self.order.len() == self.push.len() + self.pop.len() {
+            let mut pushes = self.push.iter();
+            let mut pops = self.pop.iter();
+            let events: Option<Vec<(EventType, &LinkEvent)>> = self
+                .order
+                .iter()
+                .map(|event_type| {
+                    let event = match event_type {
+                        EventType::Push => pushes.next(),
+                        EventType::Pop => pops.next(),
+                    };
+                    event.map(|event| (*event_type, event))
+                })
+                .collect();
+            if let Some(events) = events {
+                return events;
+            }
+        }
+        let pushes = self.push.iter().map(|event| (EventType::Push, event));
+        let pops = self.pop.iter().map(|event| (EventType::Pop, event));
+        let mut events: Vec<(EventType, &LinkEvent)> = pushes.chain(pops).collect();
+        events.sort_by(|(_, a), (_, b)| a.link.timestamp.total_cmp(&b.link.timestamp));
+        events
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct LinkEvent {
-    pub link: Map<String, serde_json::Value>,
-    pub queue: Map<String, serde_json::Value>,
+    pub link: Link,
+    pub queue: Queue,
+    /// Fields of the log record around the event
+    #[serde(flatten)]
+    pub envelope: Envelope,
+}
+
+/// The fields of the Bunyan record of an event, absent when the log does not have them
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Envelope {
+    /// Wall-clock time of the record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    /// Process that wrote the record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u64>,
+    /// Host that wrote the record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Bunyan level of the record, 10 for the trace level of the link queue events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u64>,
+    /// Actor that logged the record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// Name of the logger
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// A link queue event along with the query that produced it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueryEvent {
+    pub query: String,
+    /// Number of the execution of the query, starting at 1,
+    /// see [`Executions`](crate::execution::Executions)
+    #[serde(default = "first_execution")]
+    pub execution: usize,
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+    #[serde(flatten)]
+    pub event: LinkEvent,
+}
+
+fn first_execution() -> usize {
+    1
+}
+
+impl QueryEvent {
+    /// Key of the history of the execution of the query the event belongs to
+    pub fn key(&self) -> HistoryKey {
+        HistoryKey {
+            query: self.query.clone(),
+            execution: self.execution,
+        }
+    }
+}
+
+/// Identifies the history of one execution of a query
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HistoryKey {
+    /// One-line text of the query
+    pub query: String,
+    /// Number of the execution of the query, starting at 1
+    pub execution: usize,
+}
+
+impl HistoryKey {
+    /// Key of the first execution of `query`
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            execution: 1,
+        }
+    }
+}
+
+impl fmt::Display for HistoryKey {
+    /// The query followed by the number of its execution after the first one, to label the
+    /// executions in plots and tables
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.execution {
+            1 => write!(f, "{}", self.query),
+            execution => write!(f, "{} [execution {execution}]", self.query),
+        }
+    }
+}
+
+/// A record of the log about the dereferencing of a url, outside of the link queue
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceEvent {
+    pub event_type: SourceEventType,
+    pub url: String,
+    pub envelope: Envelope,
+}
+
+/// The step of the dereferencing of a url a [`SourceEvent`] reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceEventType {
+    /// `Requesting <url>`, logged by `urn:comunica:default:http/actors#fetch`
+    Request,
+    /// `Identified as file source: <url>`, logged once the document is parsed as a source
+    Identification,
+}
+
+/// The operation applied to the link queue
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum EventType {
+    Push,
+    Pop,
+}
+
+impl EventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Push => "push",
+            Self::Pop => "pop",
+        }
+    }
+}
+
+/// A link pushed into or popped from the link queue
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+    pub url: String,
+    /// The url of the document in which the link was discovered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub timestamp: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub produced_by_actor: Option<ProducedByActor>,
+    /// Fields of the link not covered by the other members
+    #[serde(flatten)]
+    pub extra: Map<String, serde_json::Value>,
+}
+
+/// The actor that extracted a link
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ProducedByActor {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Map<String, serde_json::Value>>,
+    /// Fields of the actor not covered by the other members
+    #[serde(flatten)]
+    pub extra: Map<String, serde_json::Value>,
+}
+
+/// Snapshot of the link queue after an event
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Queue {
+    pub size: u64,
+    /// Number of links pushed by each producing actor
+    pub push: BTreeMap<String, u64>,
+    /// Number of links popped by each producing actor
+    pub pop: BTreeMap<String, u64>,
+    /// Fields of the queue not covered by the other members
+    #[serde(flatten)]
+    pub extra: Map<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_preserve_unknown_fields() {
+        let raw = r#"{"url":"https://www.rubensworks.net/","timestamp":2.5,"producedByActor":{"name":"urn:comunica:default:extract-links/actors#quad-pattern-query","metadata":{"onlyVariables":true},"rank":3},"context":{"depth":1}}"#;
+
+        let link: Link = serde_json::from_str(raw).unwrap();
+
+        assert_eq!(link.url, "https://www.rubensworks.net/");
+        assert_eq!(link.parent, None);
+        assert_eq!(
+            link.extra.get("context"),
+            Some(&serde_json::json!({"depth": 1}))
+        );
+        let actor = link.produced_by_actor.as_ref().unwrap();
+        assert_eq!(
+            actor.name,
+            "urn:comunica:default:extract-links/actors#quad-pattern-query"
+        );
+        assert_eq!(actor.extra.get("rank"), Some(&serde_json::json!(3)));
+
+        let reserialized: serde_json::Value = serde_json::to_value(&link).unwrap();
+        let original: serde_json::Value = serde_json::from_str(raw).unwrap();
+        assert_eq!(reserialized, original);
+    }
+
+    #[test]
+    fn should_parse_a_queue() {
+        let raw = r#"{"size":4,"push":{"unknown":5},"pop":{"unknown":1}}"#;
+
+        let queue: Queue = serde_json::from_str(raw).unwrap();
+
+        assert_eq!(queue.size, 4);
+        assert_eq!(queue.push.get("unknown"), Some(&5));
+        assert_eq!(queue.pop.get("unknown"), Some(&1));
+        assert!(queue.extra.is_empty());
+    }
+
+    fn event(url: &str, timestamp: f64) -> LinkEvent {
+        let raw = format!(
+            r#"{{"link":{{"url":"{url}","timestamp":{timestamp}}},"queue":{{"size":0,"push":{{}},"pop":{{}}}}}}"#
+        );
+        serde_json::from_str(&raw).unwrap()
+    }
+
+    #[test]
+    fn should_keep_the_events_in_log_order() {
+        let mut history = History::default();
+        history.add(EventType::Push, event("a", 2.0));
+        history.add(EventType::Pop, event("a", 1.0));
+        history.add(EventType::Push, event("b", 3.0));
+
+        let events: Vec<(EventType, &str)> = history
+            .events()
+            .into_iter()
+            .map(|(event_type, event)| (event_type, event.link.url.as_str()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (EventType::Push, "a"),
+                (EventType::Pop, "a"),
+                (EventType::Push, "b")
+            ]
+        );
+
+        history.order.clear();
+        let urls: Vec<&str> = history
+            .events()
+            .into_iter()
+            .map(|(_, event)| event.link.url.as_str())
+            .collect();
+        assert_eq!(urls, vec!["a", "a", "b"]);
+        assert_eq!(history.events()[0].0, EventType::Pop);
+    }
 }
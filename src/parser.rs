@@ -0,0 +1,634 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
+use std::path::Path;
+use std::time::Duration;
+
+use indexmap::IndexMap;
+
+use crate::error::{Error, ParseError, ParseErrorKind};
+use crate::execution::Executions;
+use crate::filter::Filter;
+use crate::object::{History, HistoryKey, QueryEvent, SourceEvent};
+use crate::pretty::{self, PrettyRecord, PrettyRecords};
+use crate::util;
+
+/// How the parser reacts to lines that look like link queue events but cannot be parsed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Skip the line and record the error
+    #[default]
+    Lenient,
+    /// Stop at the first malformed line
+    Strict,
+}
+
+/// Format of the lines of the log
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Detect the format of each record
+    #[default]
+    Auto,
+    /// JSON records of `@comunica/logger-bunyan`
+    Bunyan,
+    /// `[<time>]  <LEVEL>: <message> <data>` records of `@comunica/logger-pretty`,
+    /// whose data may span several lines
+    Pretty,
+}
+
+/// Accumulate the link queue events of a comunica log into an [`History`] per query
+#[derive(Debug, Default)]
+pub struct LinkQueueParser {
+    history: IndexMap<HistoryKey, History>,
+    mode: Mode,
+    format: LogFormat,
+    pretty: PrettyRecords,
+    executions: Executions,
+    filter: Filter,
+    /// Whether the requests and identifications of the sources are kept
+    keep_sources: bool,
+    sources: Vec<SourceEvent>,
+    /// Events parsed and not returned yet, a line of the pretty logger can complete two records
+    queued: VecDeque<QueryEvent>,
+    line_number: usize,
+    matched_lines: usize,
+    parsed_events: usize,
+    filtered_events: usize,
+    errors: Vec<ParseError>,
+}
+
+impl LinkQueueParser {
+    /// Create a lenient parser
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_mode(mode: Mode) -> Self {
+        Self {
+            mode,
+            ..Self::default()
+        }
+    }
+
+    /// Start a new execution of a query when its events are more than `gap` apart,
+    /// by default the executions are only told apart by their process and their counters
+    pub fn with_execution_gap(mut self, gap: Duration) -> Self {
+        self.executions = Executions::new(Some(gap));
+        self
+    }
+
+    /// Read the log in the given format instead of detecting the format of each record
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Only keep the events matching `filter`
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Also keep the requests and identifications of the sources, see [`LinkQueueParser::take_sources`].
+    /// They are not filtered and do not count as link queue events
+    pub fn with_sources(mut self) -> Self {
+        self.keep_sources = true;
+        self
+    }
+
+    /// Process the next line of the log, lines unrelated to the link queue are ignored.
+    /// In lenient mode a malformed line is recorded in [`LinkQueueParser::errors`] and `Ok` is returned.
+    pub fn process_line(&mut self, line: &str) -> Result<(), ParseError> {
+        let result = self.queue_line(line);
+        self.add_queued();
+        result
+    }
+
+    fn add(&mut self, event: QueryEvent) {
+        self.history
+            .entry(event.key())
+            .or_default()
+            .add(event.event_type, event.event);
+    }
+
+    fn add_queued(&mut self) {
+        while let Some(event) = self.queued.pop_front() {
+            self.add(event);
+        }
+    }
+
+    /// Parse the next line of the log without adding it to the history, and number the execution
+    /// of the query.
+    /// Returns the oldest event not returned yet, `None` when there is none because the line is
+    /// not a link queue event, does not match the filter or is skipped in lenient mode.
+    /// The event of a record of the pretty logger is queued by the line that completes its data,
+    /// which may complete two records, so [`LinkQueueParser::next_event`] returns the rest
+    pub fn parse_line(&mut self, line: &str) -> Result<Option<QueryEvent>, ParseError> {
+        self.queue_line(line)?;
+        Ok(self.next_event())
+    }
+
+    /// Parse the next line of the log and queue its events, see [`LinkQueueParser::next_event`].
+    /// In strict mode every record completed by the line is parsed before the first error is returned
+    pub fn queue_line(&mut self, line: &str) -> Result<(), ParseError> {
+        self.line_number += 1;
+        let mut records = Vec::new();
+        let pretty = match self.format {
+            LogFormat::Bunyan => false,
+            LogFormat::Pretty => true,
+            LogFormat::Auto if self.pretty.is_pending() && util::is_bunyan_line(line) => {
+                // the data of the pending record was cut off before the Bunyan record
+                records.extend(self.pretty.finish());
+                false
+            }
+            LogFormat::Auto => self.pretty.is_pending() || pretty::is_header(line),
+        };
+        if pretty {
+            // a line completes at most the previous record, left open, and its own record
+            records.extend(self.pretty.push(self.line_number, line));
+        }
+
+        let mut first_error = None;
+        for record in records {
+            if let Err(error) = self.pretty_event(record) {
+                first_error.get_or_insert(error);
+            }
+        }
+        if !pretty {
+            let source = self
+                .keep_sources
+                .then(|| util::parse_source_line(line))
+                .flatten();
+            match source {
+                Some(source) => self.sources.push(source),
+                None => {
+                    if let Err(error) = self.accept(self.line_number, line, util::parse_line(line))
+                    {
+                        first_error.get_or_insert(error);
+                    }
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// The oldest event parsed and not returned yet
+    pub fn next_event(&mut self) -> Option<QueryEvent> {
+        self.queued.pop_front()
+    }
+
+    /// Queue the event of the record of the pretty logger whose data is still open at the end of the log
+    pub fn finish(&mut self) -> Result<(), ParseError> {
+        match self.pretty.finish() {
+            Some(record) => self.pretty_event(record),
+            None => Ok(()),
+        }
+    }
+
+    fn pretty_event(&mut self, record: PrettyRecord) -> Result<(), ParseError> {
+        if record.message != util::LINK_QUEUE_MESSAGE {
+            if self.keep_sources {
+                let source = record
+                    .bunyan()
+                    .ok()
+                    .and_then(|bunyan| util::parse_source_record(&bunyan));
+                self.sources.extend(source);
+            }
+            return Ok(());
+        }
+        let parsed = record
+            .bunyan()
+            .and_then(|bunyan| util::parse_record(&bunyan))
+            .map(Some);
+        let text = format!(
+            "[{}]  {}: {} {}",
+            record.time, record.level, record.message, record.data
+        );
+        self.accept(record.line, &text, parsed)
+    }
+
+    /// Count a parsed record, number the execution of its event, apply the filter and queue the event
+    fn accept(
+        &mut self,
+        line_number: usize,
+        line: &str,
+        parsed: Result<Option<QueryEvent>, ParseErrorKind>,
+    ) -> Result<(), ParseError> {
+        match parsed {
+            Ok(Some(mut event)) => {
+                self.matched_lines += 1;
+                // the executions are told apart by the counters of every event, even those filtered out
+                event.execution = self.executions.number(&event);
+                if !self.filter.matches(&event) {
+                    self.filtered_events += 1;
+                    return Ok(());
+                }
+                self.parsed_events += 1;
+                self.queued.push_back(event);
+                Ok(())
+            }
+            Ok(None) => Ok(()),
+            Err(kind) => {
+                self.matched_lines += 1;
+                let error = ParseError::new(line_number, kind, line);
+                match self.mode {
+                    Mode::Lenient => {
+                        self.errors.push(error);
+                        Ok(())
+                    }
+                    Mode::Strict => Err(error),
+                }
+            }
+        }
+    }
+
+    /// Process every line of `reader`
+    pub fn read<R: BufRead>(&mut self, reader: R) -> Result<(), Error> {
+        for line in reader.lines() {
+            self.process_line(&line?)?;
+        }
+        let result = self.finish();
+        self.add_queued();
+        Ok(result?)
+    }
+
+    /// Iterate over the link queue events of `reader` as they are read, without keeping them in the history
+    pub fn events<R: BufRead>(&mut self, reader: R) -> Events<'_, R> {
+        Events {
+            parser: self,
+            lines: reader.lines(),
+            finished: false,
+        }
+    }
+
+    /// The history of every execution of a query parsed so far, keyed by [`QueryEvent::key`]
+    pub fn history(&self) -> &IndexMap<HistoryKey, History> {
+        &self.history
+    }
+
+    /// Remove and return the source events read so far, empty unless [`LinkQueueParser::with_sources`] is set
+    pub fn take_sources(&mut self) -> Vec<SourceEvent> {
+        std::mem::take(&mut self.sources)
+    }
+
+    /// The malformed lines skipped in lenient mode
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// The number of lines processed so far
+    pub fn line_count(&self) -> usize {
+        self.line_number
+    }
+
+    /// Summary of the lines processed so far
+    pub fn diagnostics(&self) -> Diagnostics {
+        let mut skipped_lines: BTreeMap<String, SkippedLines> = BTreeMap::new();
+        for error in &self.errors {
+            skipped_lines
+                .entry(error.kind.reason())
+                .or_insert(SkippedLines {
+                    count: 0,
+                    first_line: error.line,
+                })
+                .count += 1;
+        }
+        Diagnostics {
+            lines: self.line_number,
+            matched_lines: self.matched_lines,
+            parsed_events: self.parsed_events,
+            filtered_events: self.filtered_events,
+            skipped_lines,
+        }
+    }
+
+    pub fn into_history(self) -> IndexMap<HistoryKey, History> {
+        self.history
+    }
+}
+
+/// Iterator over the link queue events of a log, see [`LinkQueueParser::events`]
+pub struct Events<'a, R> {
+    parser: &'a mut LinkQueueParser,
+    lines: Lines<R>,
+    finished: bool,
+}
+
+impl<R: BufRead> Iterator for Events<'_, R> {
+    type Item = Result<QueryEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.parser.next_event() {
+                return Some(Ok(event));
+            }
+            let result = match self.lines.next() {
+                Some(Ok(line)) => self.parser.queue_line(&line),
+                Some(Err(error)) => return Some(Err(error.into())),
+                None if self.finished => return None,
+                None => {
+                    self.finished = true;
+                    self.parser.finish()
+                }
+            };
+            if let Err(error) = result {
+                return Some(Err(error.into()));
+            }
+        }
+    }
+}
+
+/// Summary of a parsing run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostics {
+    /// Number of lines read
+    pub lines: usize,
+    /// Number of lines that look like link queue events
+    pub matched_lines: usize,
+    /// Number of link queue events added to the history
+    pub parsed_events: usize,
+    /// Number of link queue events that did not match the filter
+    pub filtered_events: usize,
+    /// Lines skipped in lenient mode, grouped by reason
+    pub skipped_lines: BTreeMap<String, SkippedLines>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLines {
+    pub count: usize,
+    /// Line number of the first skipped line with this reason
+    pub first_line: usize,
+}
+
+impl Diagnostics {
+    pub fn skipped_line_count(&self) -> usize {
+        self.skipped_lines
+            .values()
+            .map(|skipped| skipped.count)
+            .sum()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lines read: {}", self.lines)?;
+        writeln!(f, "matched lines: {}", self.matched_lines)?;
+        writeln!(f, "parsed events: {}", self.parsed_events)?;
+        if self.filtered_events > 0 {
+            writeln!(f, "filtered events: {}", self.filtered_events)?;
+        }
+        write!(f, "skipped lines: {}", self.skipped_line_count())?;
+        for (reason, skipped) in &self.skipped_lines {
+            write!(
+                f,
+                "\n  {reason}: {} (first at line {})",
+                skipped.count, skipped.first_line
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse every line of a comunica log, lines that cannot be parsed are skipped
+pub fn parse_reader<R: BufRead>(reader: R) -> io::Result<IndexMap<HistoryKey, History>> {
+    let mut parser = LinkQueueParser::new();
+    parser.read(reader)?;
+    Ok(parser.into_history())
+}
+
+/// Parse the comunica log located at `path`
+pub fn parse_file<P: AsRef<Path>>(path: P) -> io::Result<IndexMap<HistoryKey, History>> {
+    let file = File::open(path)?;
+    parse_reader(BufReader::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorKind;
+    use crate::object::EventType;
+
+    const LOG: &str = concat!(
+        r#"{"name":"comunica","pid":17725,"level":30,"msg":"Requesting https://www.rubensworks.net/","time":"2024-07-05T12:06:08.501Z","v":0}"#,
+        "\n",
+        r#"{"name":"comunica","pid":17725,"level":10,"data":{"type":"push","link":{"url":"https://www.rubensworks.net/a","timestamp":1.0},"query":"SELECT * WHERE {\n ?s ?p ?o.\n}","queue":{"size":1,"push":{"unknown":1},"pop":{}}},"msg":"Link queue changed","time":"2024-07-05T12:06:08.654Z","v":0}"#,
+        "\n",
+        r#"{"name":"comunica","pid":17725,"level":10,"data":{"type":"push","link":{"url":"https://www.rubensworks.net/b","timestamp":1.5},"queue":{"size":2,"push":{"unknown":2},"pop":{}}},"msg":"Link queue changed","time":"2024-07-05T12:06:08.654Z","v":0}"#,
+        "\n",
+        r#"{"name":"comunica","pid":17725,"level":10,"data":{"type":"pop","link":{"url":"https://www.rubensworks.net/a","timestamp":2.0},"query":"SELECT * WHERE {\n ?s ?p ?o.\n}","queue":{"size":0,"push":{"unknown":1},"pop":{"unknown":1}}},"msg":"Link queue changed","time":"2024-07-05T12:06:08.655Z","v":0}"#,
+        "\n",
+    );
+
+    #[test]
+    fn should_parse_a_reader() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+
+        assert_eq!(history.len(), 1);
+        let query_history = history
+            .get(&HistoryKey::new("SELECT * WHERE {  ?s ?p ?o. }"))
+            .unwrap();
+        assert_eq!(query_history.push.len(), 1);
+        assert_eq!(query_history.pop.len(), 1);
+    }
+
+    #[test]
+    fn should_keep_a_history_per_execution() {
+        let log = LOG.repeat(2);
+
+        let history = parse_reader(log.as_bytes()).unwrap();
+
+        assert_eq!(
+            history
+                .keys()
+                .map(|key| (key.query.as_str(), key.execution))
+                .collect::<Vec<_>>(),
+            vec![
+                ("SELECT * WHERE {  ?s ?p ?o. }", 1),
+                ("SELECT * WHERE {  ?s ?p ?o. }", 2)
+            ]
+        );
+        assert!(history.values().all(|history| history.pop.len() == 1));
+    }
+
+    #[test]
+    fn should_parse_the_pretty_format() {
+        let log = concat!(
+            "[2024-07-05T12:06:08.501Z]  INFO: Requesting https://www.rubensworks.net/ { actor: 'urn:comunica:default:http/actors#fetch' }\n",
+            "[2024-07-05T12:06:08.654Z]  TRACE: Link queue changed {\n",
+            "  data: {\n",
+            "    type: 'push',\n",
+            "    link: { url: 'https://www.rubensworks.net/a', timestamp: 1 },\n",
+            "    query: 'SELECT * WHERE {\\n ?s ?p ?o.\\n}',\n",
+            "    queue: { size: 1, push: { unknown: 1 }, pop: {} }\n",
+            "  }\n",
+            "}\n",
+        );
+        let mixed = format!(
+            "{log}{}",
+            r#"{"level":10,"data":{"type":"push","link":{"url":"https://www.rubensworks.net/b","timestamp":2.0},"query":"ASK { ?s ?p ?o }","queue":{"size":1,"push":{"unknown":1},"pop":{}}},"msg":"Link queue changed"}"#
+        );
+        let mut parser = LinkQueueParser::new();
+
+        parser.read(mixed.as_bytes()).unwrap();
+
+        let history = parser.history();
+        assert_eq!(
+            history
+                .keys()
+                .map(|key| key.query.as_str())
+                .collect::<Vec<_>>(),
+            vec!["SELECT * WHERE {  ?s ?p ?o. }", "ASK { ?s ?p ?o }"]
+        );
+        let query_history = &history[&HistoryKey::new("SELECT * WHERE {  ?s ?p ?o. }")];
+        let pushed = &query_history.push[0];
+        assert_eq!(pushed.link.url, "https://www.rubensworks.net/a");
+        assert_eq!(
+            pushed.envelope.time.as_deref(),
+            Some("2024-07-05T12:06:08.654Z")
+        );
+        assert_eq!(pushed.envelope.level, Some(10));
+
+        let mut with_sources = LinkQueueParser::new().with_sources();
+        with_sources.read(log.as_bytes()).unwrap();
+        let sources = with_sources.take_sources();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].url, "https://www.rubensworks.net/");
+        assert_eq!(
+            sources[0].envelope.actor.as_deref(),
+            Some("urn:comunica:default:http/actors#fetch")
+        );
+
+        let mut bunyan = LinkQueueParser::new().with_format(LogFormat::Bunyan);
+        bunyan.read(log.as_bytes()).unwrap();
+        assert!(bunyan.history().is_empty());
+    }
+
+    #[test]
+    fn should_report_a_truncated_pretty_record() {
+        let log = "[2024-07-05T12:06:08.654Z]  TRACE: Link queue changed {\n  data: {\n";
+        let mut parser = LinkQueueParser::new();
+
+        let events: Vec<QueryEvent> = parser
+            .events(log.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert!(events.is_empty());
+        assert_eq!(parser.errors().len(), 1);
+        assert_eq!(parser.errors()[0].line, 1);
+    }
+
+    #[test]
+    fn should_keep_the_events_after_a_truncated_pretty_record() {
+        let truncated = "[2024-07-05T12:06:08.654Z]  TRACE: Link queue changed {\n  data: {\n";
+        let pretty = "[2024-07-05T12:06:08.655Z]  TRACE: Link queue changed { data: { type: 'push', link: { url: 'https://www.rubensworks.net/b', timestamp: 2 }, query: 'ASK { ?s ?p ?o }', queue: { size: 1, push: { unknown: 1 }, pop: {} } } }\n";
+        let bunyan = r#"{"level":10,"data":{"type":"push","link":{"url":"https://www.rubensworks.net/c","timestamp":3.0},"query":"ASK { ?s ?p ?o }","queue":{"size":2,"push":{"unknown":2},"pop":{}}},"msg":"Link queue changed"}"#;
+
+        // the Bunyan record is not taken for the data of the truncated record
+        let mut parser = LinkQueueParser::new();
+        parser
+            .read(format!("{truncated}{bunyan}\n").as_bytes())
+            .unwrap();
+        assert_eq!(
+            parser.history()[&HistoryKey::new("ASK { ?s ?p ?o }")]
+                .push
+                .len(),
+            1
+        );
+        assert_eq!(parser.errors()[0].line, 1);
+
+        // the line that reports the truncated record in strict mode still queues its own event
+        let mut strict = LinkQueueParser::with_mode(Mode::Strict);
+        let events: Vec<Result<QueryEvent, Error>> = strict
+            .events(format!("{truncated}{pretty}{bunyan}\n").as_bytes())
+            .collect();
+        assert!(matches!(&events[0], Err(Error::Parse(error)) if error.line == 1));
+        let urls: Vec<&str> = events[1..]
+            .iter()
+            .map(|event| event.as_ref().unwrap().event.link.url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://www.rubensworks.net/b",
+                "https://www.rubensworks.net/c"
+            ]
+        );
+    }
+
+    #[test]
+    fn should_collect_errors_in_lenient_mode() {
+        let mut parser = LinkQueueParser::new();
+
+        parser.read(LOG.as_bytes()).unwrap();
+
+        assert_eq!(parser.line_count(), 4);
+        assert_eq!(parser.errors().len(), 1);
+        let error = &parser.errors()[0];
+        assert_eq!(error.line, 3);
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MissingField("data.query")
+        ));
+        assert!(error.snippet.starts_with(r#"{"name":"comunica""#));
+    }
+
+    #[test]
+    fn should_iterate_over_the_events() {
+        let mut parser = LinkQueueParser::new();
+
+        let events: Vec<QueryEvent> = parser
+            .events(LOG.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|event| (event.event_type, event.event.link.url.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (EventType::Push, "https://www.rubensworks.net/a"),
+                (EventType::Pop, "https://www.rubensworks.net/a"),
+            ]
+        );
+        assert!(parser.history().is_empty());
+        assert_eq!(parser.diagnostics().parsed_events, 2);
+        assert_eq!(parser.errors().len(), 1);
+    }
+
+    #[test]
+    fn should_summarize_the_run() {
+        let mut parser = LinkQueueParser::new();
+
+        parser.read(LOG.as_bytes()).unwrap();
+        let diagnostics = parser.diagnostics();
+
+        assert_eq!(diagnostics.lines, 4);
+        assert_eq!(diagnostics.matched_lines, 3);
+        assert_eq!(diagnostics.parsed_events, 2);
+        assert_eq!(diagnostics.skipped_line_count(), 1);
+        assert_eq!(
+            diagnostics.skipped_lines.get("missing field `data.query`"),
+            Some(&SkippedLines {
+                count: 1,
+                first_line: 3
+            })
+        );
+    }
+
+    #[test]
+    fn should_stop_at_the_first_error_in_strict_mode() {
+        let mut parser = LinkQueueParser::with_mode(Mode::Strict);
+
+        let error = parser.read(LOG.as_bytes()).unwrap_err();
+
+        match error {
+            Error::Parse(error) => assert_eq!(error.line, 3),
+            Error::Io(error) => panic!("unexpected io error {error}"),
+        }
+        assert_eq!(parser.history().len(), 1);
+        assert!(parser.errors().is_empty());
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use chrono::DateTime;
+
+use crate::object::{History, LinkEvent};
+use crate::run::Run;
+
+/// Quantity of the horizontal axis of a plot
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TimeAxis {
+    /// `link.timestamp`, in milliseconds since the start of the engine
+    #[default]
+    Relative,
+    /// The `time` field of Bunyan, events without it are left out
+    WallClock,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlotOptions {
+    pub axis: TimeAxis,
+    /// Also plot the cumulative pushes and pops of each actor
+    pub actors: bool,
+    /// Draw every query of every run in the same chart instead of one chart per query
+    pub overlay: bool,
+}
+
+const WIDTH: f64 = 800.0;
+const PLOT_HEIGHT: f64 = 260.0;
+const LEFT: f64 = 70.0;
+const RIGHT: f64 = 20.0;
+const TOP: f64 = 30.0;
+const BOTTOM: f64 = 40.0;
+const LEGEND_LINE: f64 = 16.0;
+/// Number of characters of a series name shown in the legend
+const LEGEND_LENGTH: usize = 110;
+const PALETTE: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    "#bcbd22", "#17becf",
+];
+
+struct Series {
+    name: String,
+    color: usize,
+    dashed: bool,
+    points: Vec<(f64, f64)>,
+}
+
+struct Panel {
+    title: String,
+    y_label: &'static str,
+    series: Vec<Series>,
+}
+
+impl Panel {
+    fn height(&self) -> f64 {
+        TOP + PLOT_HEIGHT + BOTTOM + self.series.len() as f64 * LEGEND_LINE
+    }
+}
+
+fn x_value(event: &LinkEvent, axis: TimeAxis) -> Option<f64> {
+    match axis {
+        TimeAxis::Relative => Some(event.link.timestamp),
+        TimeAxis::WallClock => event
+            .envelope
+            .time
+            .as_deref()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map(|time| time.timestamp_millis() as f64),
+    }
+}
+
+fn size_series(name: String, color: usize, history: &History, axis: TimeAxis) -> Series {
+    Series {
+        name,
+        color,
+        dashed: false,
+        points: history
+            .events()
+            .into_iter()
+            .filter_map(|(_, event)| Some((x_value(event, axis)?, event.queue.size as f64)))
+            .collect(),
+    }
+}
+
+/// A solid series of the pushes and a dashed series of the pops of each actor, colored from `color`
+fn actor_series(
+    prefix: Option<&str>,
+    color: &mut usize,
+    history: &History,
+    axis: TimeAxis,
+) -> Vec<Series> {
+    let events = history.events();
+    let actors: BTreeSet<&String> = events
+        .iter()
+        .flat_map(|(_, event)| event.queue.push.keys().chain(event.queue.pop.keys()))
+        .collect();
+    let mut series = Vec::new();
+    for actor in actors {
+        for (kind, dashed) in [("push", false), ("pop", true)] {
+            let points = events
+                .iter()
+                .filter_map(|(_, event)| {
+                    let counters = if dashed {
+                        &event.queue.pop
+                    } else {
+                        &event.queue.push
+                    };
+                    let count = counters.get(actor).copied().unwrap_or(0);
+                    Some((x_value(event, axis)?, count as f64))
+                })
+                .collect();
+            series.push(Series {
+                name: match prefix {
+                    Some(prefix) => format!("{kind} {actor} ({prefix})"),
+                    None => format!("{kind} {actor}"),
+                },
+                color: *color,
+                dashed,
+                points,
+            });
+        }
+        *color += 1;
+    }
+    series
+}
+
+fn panels(runs: &[Run], options: &PlotOptions) -> Vec<Panel> {
+    let queries: Vec<(String, &History)> = runs
+        .iter()
+        .flat_map(|run| {
+            run.history
+                .iter()
+                .map(|(key, history)| (run.name(key), history))
+        })
+        .collect();
+
+    let mut panels = Vec::new();
+    if options.overlay {
+        panels.push(Panel {
+            title: "queue size".to_string(),
+            y_label: "links in the queue",
+            series: queries
+                .iter()
+                .enumerate()
+                .map(|(index, (name, history))| {
+                    size_series(name.clone(), index, history, options.axis)
+                })
+                .collect(),
+        });
+        if options.actors {
+            let mut color = 0;
+            panels.push(Panel {
+                title: "cumulative pushes and pops per actor".to_string(),
+                y_label: "links",
+                series: queries
+                    .iter()
+                    .flat_map(|(name, history)| {
+                        actor_series(Some(name), &mut color, history, options.axis)
+                    })
+                    .collect(),
+            });
+        }
+    } else {
+        for (name, history) in &queries {
+            panels.push(Panel {
+                title: format!("queue size: {name}"),
+                y_label: "links in the queue",
+                series: vec![size_series(name.clone(), 0, history, options.axis)],
+            });
+            if options.actors {
+                panels.push(Panel {
+                    title: format!("cumulative pushes and pops per actor: {name}"),
+                    y_label: "links",
+                    series: actor_series(None, &mut 0, history, options.axis),
+                });
+            }
+        }
+    }
+    for panel in &mut panels {
+        panel.series.retain(|series| !series.points.is_empty());
+    }
+    panels
+}
+
+/// Round values between `min` and `max`, about `count` of them
+fn ticks(min: f64, max: f64, count: usize) -> Vec<f64> {
+    let raw_step = (max - min) / count as f64;
+    let magnitude = 10f64.powf(raw_step.log10().floor());
+    let intervals = |step: f64| ((max - min) / step - count as f64).abs();
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .min_by(|a, b| intervals(*a).total_cmp(&intervals(*b)))
+        .unwrap_or(magnitude);
+    let mut tick = (min / step).ceil() * step;
+    let mut ticks = Vec::new();
+    while tick <= max + step * 1e-9 {
+        ticks.push(tick);
+        tick += step;
+    }
+    ticks
+}
+
+fn number_label(value: f64, step: f64) -> String {
+    let decimals = if step >= 1.0 {
+        0
+    } else {
+        (-step.log10()).ceil() as usize
+    };
+    format!("{value:.decimals$}")
+}
+
+fn time_label(value: f64, axis: TimeAxis, step: f64) -> String {
+    match axis {
+        TimeAxis::Relative => number_label(value, step),
+        TimeAxis::WallClock => DateTime::from_timestamp_millis(value as i64)
+            .map(|time| {
+                if step >= 1000.0 {
+                    time.format("%H:%M:%S").to_string()
+                } else {
+                    time.format("%H:%M:%S%.3f").to_string()
+                }
+            })
+            .unwrap_or_default(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(LEGEND_LENGTH) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn draw_panel(
+    svg: &mut String,
+    panel: &Panel,
+    top: f64,
+    (min_x, max_x): (f64, f64),
+    axis: TimeAxis,
+) {
+    let max_y = panel
+        .series
+        .iter()
+        .flat_map(|series| &series.points)
+        .map(|(_, y)| *y)
+        .fold(1.0, f64::max);
+    let bottom = top + TOP + PLOT_HEIGHT;
+    let x = |value: f64| LEFT + (value - min_x) / (max_x - min_x) * (WIDTH - LEFT - RIGHT);
+    let y = |value: f64| bottom - value / max_y * PLOT_HEIGHT;
+
+    let _ = writeln!(
+        svg,
+        r#"<text x="{LEFT}" y="{}" font-weight="bold">{}</text>"#,
+        top + TOP - 12.0,
+        escape(&truncate(&panel.title))
+    );
+    let _ = writeln!(
+        svg,
+        r##"<rect x="{LEFT}" y="{}" width="{}" height="{PLOT_HEIGHT}" fill="none" stroke="#999"/>"##,
+        top + TOP,
+        WIDTH - LEFT - RIGHT
+    );
+
+    // the sizes and counters are integers, so small ranges get fewer ticks
+    let y_ticks = ticks(0.0, max_y, (max_y as usize).clamp(1, 5));
+    let y_step = y_ticks.get(1).map_or(1.0, |second| second - y_ticks[0]);
+    for tick in &y_ticks {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{LEFT}" y1="{0:.1}" x2="{1}" y2="{0:.1}" stroke="#eee"/><text x="{2}" y="{3:.1}" text-anchor="end">{4}</text>"##,
+            y(*tick),
+            WIDTH - RIGHT,
+            LEFT - 6.0,
+            y(*tick) + 4.0,
+            number_label(*tick, y_step)
+        );
+    }
+    let x_ticks = ticks(min_x, max_x, 8);
+    let x_step = x_ticks.get(1).map_or(1.0, |second| second - x_ticks[0]);
+    for tick in &x_ticks {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{0:.1}" y1="{bottom}" x2="{0:.1}" y2="{1}" stroke="#999"/><text x="{0:.1}" y="{2}" text-anchor="middle">{3}</text>"##,
+            x(*tick),
+            bottom + 4.0,
+            bottom + 16.0,
+            time_label(*tick, axis, x_step)
+        );
+    }
+    let x_label = match axis {
+        TimeAxis::Relative => "link.timestamp (ms)".to_string(),
+        TimeAxis::WallClock => match DateTime::from_timestamp_millis(min_x as i64) {
+            Some(start) => format!("time (UTC, {})", start.format("%Y-%m-%d")),
+            None => "time (UTC)".to_string(),
+        },
+    };
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="middle">{x_label}</text>"#,
+        LEFT + (WIDTH - LEFT - RIGHT) / 2.0,
+        bottom + 32.0
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text transform="translate(14 {}) rotate(-90)" text-anchor="middle">{}</text>"#,
+        top + TOP + PLOT_HEIGHT / 2.0,
+        panel.y_label
+    );
+
+    for (index, series) in panel.series.iter().enumerate() {
+        let color = PALETTE[series.color % PALETTE.len()];
+        let dash = if series.dashed {
+            r#" stroke-dasharray="4 3""#
+        } else {
+            ""
+        };
+        // the queue size and the counters hold their value until the next event
+        let mut path = String::new();
+        for (point, (px, py)) in series.points.iter().enumerate() {
+            if point == 0 {
+                let _ = write!(path, "M{:.1} {:.1}", x(*px), y(*py));
+            } else {
+                let _ = write!(path, "H{:.1}V{:.1}", x(*px), y(*py));
+            }
+        }
+        let _ = writeln!(
+            svg,
+            r#"<path d="{path}" fill="none" stroke="{color}" stroke-width="1.2"{dash}/>"#
+        );
+        let legend_y = bottom + BOTTOM + index as f64 * LEGEND_LINE + 10.0;
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{LEFT}" y1="{0}" x2="{1}" y2="{0}" stroke="{color}" stroke-width="2"{dash}/><text x="{2}" y="{3}">{4}</text>"#,
+            legend_y - 4.0,
+            LEFT + 20.0,
+            LEFT + 26.0,
+            legend_y,
+            escape(&truncate(&series.name))
+        );
+    }
+}
+
+/// Write an SVG image of the queue size over time, with one chart per query or a single chart
+/// for every query, and optionally the cumulative pushes and pops of each actor.
+/// All charts share the same horizontal axis.
+pub fn write_svg<W: Write>(runs: &[Run], options: &PlotOptions, mut writer: W) -> io::Result<()> {
+    let panels = panels(runs, options);
+    let xs = panels
+        .iter()
+        .flat_map(|panel| &panel.series)
+        .flat_map(|series| &series.points)
+        .map(|(x, _)| *x);
+    let min_x = xs.clone().fold(f64::INFINITY, f64::min);
+    let max_x = xs.fold(f64::NEG_INFINITY, f64::max);
+    let (min_x, max_x) = match (min_x.is_finite(), max_x > min_x) {
+        (false, _) => (0.0, 1.0),
+        (true, false) => (min_x, min_x + 1.0),
+        (true, true) => (min_x, max_x),
+    };
+    let height = panels.iter().map(Panel::height).sum::<f64>().max(TOP);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" viewBox="0 0 {WIDTH} {height}" font-family="sans-serif" font-size="11">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{WIDTH}" height="{height}" fill="white"/>"#
+    );
+    let mut top = 0.0;
+    for panel in &panels {
+        draw_panel(&mut svg, panel, top, (min_x, max_x), options.axis);
+        top += panel.height();
+    }
+    svg.push_str("</svg>\n");
+    writer.write_all(svg.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_reader;
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":10.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1},"pop":{}}},"msg":"Link queue changed","time":"2024-08-07T08:21:44.100Z"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/b","timestamp":20.0},"query":"ASK { ?s ?p ?o }","queue":{"size":1,"push":{"actor-b":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://example.org/a","timestamp":30.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":0,"push":{"actor-a":1},"pop":{"actor-a":1}}},"msg":"Link queue changed","time":"2024-08-07T08:21:44.300Z"}"#,
+        "\n",
+    );
+
+    fn run() -> Run {
+        Run::new(None, parse_reader(LOG.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn should_draw_a_chart_per_query() {
+        let options = PlotOptions {
+            actors: true,
+            ..PlotOptions::default()
+        };
+
+        let panels = panels(&[run()], &options);
+
+        let titles: Vec<&str> = panels.iter().map(|panel| panel.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "queue size: SELECT * WHERE { ?s ?p ?o }",
+                "cumulative pushes and pops per actor: SELECT * WHERE { ?s ?p ?o }",
+                "queue size: ASK { ?s ?p ?o }",
+                "cumulative pushes and pops per actor: ASK { ?s ?p ?o }",
+            ]
+        );
+        assert_eq!(panels[0].series[0].points, vec![(10.0, 1.0), (30.0, 0.0)]);
+        let names: Vec<&str> = panels[1]
+            .series
+            .iter()
+            .map(|series| series.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["push actor-a", "pop actor-a"]);
+        assert_eq!(panels[1].series[1].points, vec![(10.0, 0.0), (30.0, 1.0)]);
+    }
+
+    #[test]
+    fn should_overlay_the_queries_on_the_wall_clock() {
+        let options = PlotOptions {
+            axis: TimeAxis::WallClock,
+            overlay: true,
+            ..PlotOptions::default()
+        };
+
+        let panels = panels(&[run()], &options);
+
+        assert_eq!(panels.len(), 1);
+        // the events of the second query have no Bunyan time
+        assert_eq!(panels[0].series.len(), 1);
+        assert_eq!(
+            panels[0].series[0].points,
+            vec![(1723018904100.0, 1.0), (1723018904300.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn should_write_an_svg_image() {
+        let mut output = Vec::new();
+
+        write_svg(&[run()], &PlotOptions::default(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(output.ends_with("</svg>\n"));
+        assert_eq!(output.matches("<path ").count(), 2);
+    }
+
+    #[test]
+    fn should_choose_round_ticks() {
+        assert_eq!(ticks(0.0, 10.0, 5), vec![0.0, 2.0, 4.0, 6.0, 8.0, 10.0]);
+        assert_eq!(
+            ticks(12.0, 130.0, 5),
+            vec![20.0, 40.0, 60.0, 80.0, 100.0, 120.0]
+        );
+    }
+}
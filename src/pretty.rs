@@ -0,0 +1,444 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::error::ParseErrorKind;
+
+lazy_static! {
+    /// Escape sequences coloring the output of the pretty logger
+    static ref RE_COLOR: Regex = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+    /// First line of a record, `[<time>]  <LEVEL>: <message> <data>`
+    static ref RE_HEADER: Regex =
+        Regex::new(r"^\[(?<time>[^\]]+)\]\s+(?<level>TRACE|DEBUG|INFO|WARN|ERROR|FATAL):\s?(?<rest>.*)$")
+            .unwrap();
+}
+
+/// Whether a line starts a record of the comunica pretty logger
+pub fn is_header(line: &str) -> bool {
+    RE_HEADER.is_match(&RE_COLOR.replace_all(line, ""))
+}
+
+/// A record of the pretty logger, whose data may span several lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrettyRecord {
+    /// Line number of the first line of the record
+    pub line: usize,
+    pub time: String,
+    pub level: String,
+    pub message: String,
+    /// The data of the record as printed by `util.inspect`, empty when the record has none
+    pub data: String,
+}
+
+impl PrettyRecord {
+    /// The record in the shape of a Bunyan record, the data with `msg`, `time` and `level` fields
+    pub fn bunyan(&self) -> Result<Map<String, Value>, ParseErrorKind> {
+        let mut record = if self.data.trim().is_empty() {
+            Map::new()
+        } else {
+            serde_json::from_str(&inspected_to_json(&self.data))
+                .map_err(ParseErrorKind::InvalidJson)?
+        };
+        record.insert("msg".to_string(), Value::from(self.message.as_str()));
+        record.insert("time".to_string(), Value::from(self.time.as_str()));
+        if let Some(level) = bunyan_level(&self.level) {
+            record.insert("level".to_string(), Value::from(level));
+        }
+        Ok(record)
+    }
+}
+
+/// Bunyan level of a level of the pretty logger
+fn bunyan_level(level: &str) -> Option<u64> {
+    match level {
+        "TRACE" => Some(10),
+        "DEBUG" => Some(20),
+        "INFO" => Some(30),
+        "WARN" => Some(40),
+        "ERROR" => Some(50),
+        "FATAL" => Some(60),
+        _ => None,
+    }
+}
+
+/// Gather the lines of the records of the pretty logger
+#[derive(Debug, Default)]
+pub struct PrettyRecords {
+    pending: Option<(PrettyRecord, Balance)>,
+}
+
+impl PrettyRecords {
+    /// Whether a record waits for the end of its data
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Add the next line of the log, returns the records that this line completes.
+    /// A record whose data is still open when the next record starts is returned as is.
+    pub fn push(&mut self, line_number: usize, line: &str) -> Vec<PrettyRecord> {
+        let line = RE_COLOR.replace_all(line, "");
+        let mut records = Vec::new();
+        match RE_HEADER.captures(&line) {
+            Some(caps) => {
+                records.extend(self.finish());
+                let rest = &caps["rest"];
+                let (message, data) = match rest.find('{') {
+                    Some(start) => (rest[..start].trim_end(), &rest[start..]),
+                    None => (rest.trim_end(), ""),
+                };
+                let record = PrettyRecord {
+                    line: line_number,
+                    time: caps["time"].to_string(),
+                    level: caps["level"].to_string(),
+                    message: message.to_string(),
+                    data: data.to_string(),
+                };
+                let mut balance = Balance::default();
+                balance.feed(data);
+                if balance.is_closed() {
+                    records.push(record);
+                } else {
+                    self.pending = Some((record, balance));
+                }
+            }
+            None => {
+                if let Some((record, balance)) = self.pending.as_mut() {
+                    record.data.push('\n');
+                    record.data.push_str(&line);
+                    balance.feed(&line);
+                    if balance.is_closed() {
+                        records.extend(self.finish());
+                    }
+                }
+            }
+        }
+        records
+    }
+
+    /// The pending record, at the end of the log
+    pub fn finish(&mut self) -> Option<PrettyRecord> {
+        self.pending.take().map(|(record, _)| record)
+    }
+}
+
+/// Depth of the brackets of the data of a record, outside of its strings
+#[derive(Debug, Default)]
+struct Balance {
+    depth: usize,
+    quote: Option<char>,
+    escaped: bool,
+}
+
+impl Balance {
+    fn feed(&mut self, text: &str) {
+        for c in text.chars() {
+            match self.quote {
+                Some(_) if self.escaped => self.escaped = false,
+                Some(_) if c == '\\' => self.escaped = true,
+                Some(quote) if c == quote => self.quote = None,
+                Some(_) => {}
+                None => match c {
+                    '\'' | '"' | '`' => self.quote = Some(c),
+                    '{' | '[' => self.depth += 1,
+                    '}' | ']' => self.depth = self.depth.saturating_sub(1),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.depth == 0 && self.quote.is_none()
+    }
+}
+
+/// Rewrite the output of `util.inspect` as JSON: the keys and strings are quoted with double quotes,
+/// the trailing commas, class names and `... n more items` are removed and the values that JSON
+/// cannot represent, such as `undefined` or `[Object]` beyond the inspected depth, become `null`.
+/// The data of a Bunyan style logger is already JSON and is left unchanged.
+fn inspected_to_json(text: &str) -> String {
+    let mut json = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut comma = false;
+    let mut containers: Vec<char> = Vec::new();
+    // whether the next token is a key, right after `{` or after a `,` in an object
+    let mut expects_key = false;
+
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '{' | '[' => {
+                chars.next();
+                if c == '[' && chars.peek().is_some_and(|next| next.is_alphabetic()) {
+                    skip_until(&mut chars, ']');
+                    "null".to_string()
+                } else {
+                    containers.push(c);
+                    expects_key = c == '{';
+                    if comma {
+                        json.push(',');
+                        comma = false;
+                    }
+                    json.push(c);
+                    continue;
+                }
+            }
+            '}' | ']' => {
+                chars.next();
+                containers.pop();
+                comma = false;
+                expects_key = false;
+                json.push(c);
+                continue;
+            }
+            ',' => {
+                chars.next();
+                comma = true;
+                expects_key = containers.last() == Some(&'{');
+                continue;
+            }
+            ':' => {
+                chars.next();
+                json.push(':');
+                expects_key = false;
+                continue;
+            }
+            '<' => {
+                // a reference marker such as `<ref *1>`
+                skip_until(&mut chars, '>');
+                continue;
+            }
+            '.' => {
+                // `... 12 more items`
+                while chars
+                    .next_if(|next| !matches!(next, ',' | '}' | ']'))
+                    .is_some()
+                {}
+                continue;
+            }
+            '\'' | '"' | '`' => {
+                let mut string = read_string(&mut chars);
+                // long strings are split as `'...' +\n '...'`
+                loop {
+                    let mut lookahead = chars.clone();
+                    while lookahead.next_if(|next| next.is_whitespace()).is_some() {}
+                    if lookahead.next() != Some('+') {
+                        break;
+                    }
+                    while lookahead.next_if(|next| next.is_whitespace()).is_some() {}
+                    if !lookahead
+                        .peek()
+                        .is_some_and(|next| matches!(next, '\'' | '"' | '`'))
+                    {
+                        break;
+                    }
+                    chars = lookahead;
+                    string.push_str(&read_string(&mut chars));
+                }
+                serde_json::to_string(&string).expect("a string is serializable")
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(next) = chars.next_if(|next| {
+                    next.is_alphanumeric() || matches!(next, '_' | '$' | '-' | '+' | '.')
+                }) {
+                    word.push(next);
+                }
+                if word.is_empty() {
+                    // a character that cannot start a value is kept for the JSON parser to report it
+                    chars.next();
+                    json.push(c);
+                    continue;
+                }
+                if expects_key {
+                    serde_json::to_string(&word).expect("a string is serializable")
+                } else {
+                    let mut lookahead = chars.clone();
+                    while lookahead.next_if(|next| next.is_whitespace()).is_some() {}
+                    match lookahead.peek() {
+                        // the name of the class of an object or of the size of a collection, `Map(2) {`
+                        Some('{') | Some('[') => continue,
+                        Some('(') => {
+                            chars = lookahead;
+                            skip_until(&mut chars, ')');
+                            let mut lookahead = chars.clone();
+                            while lookahead.next_if(|next| next.is_whitespace()).is_some() {}
+                            if matches!(lookahead.peek(), Some('{') | Some('[')) {
+                                continue;
+                            }
+                            "null".to_string()
+                        }
+                        _ => literal(&word),
+                    }
+                }
+            }
+        };
+        if comma {
+            json.push(',');
+            comma = false;
+        }
+        json.push_str(&token);
+    }
+    json
+}
+
+/// A word of `util.inspect` as a JSON value
+fn literal(word: &str) -> String {
+    match word {
+        "true" | "false" | "null" => word.to_string(),
+        _ => {
+            let number = word.strip_suffix('n').unwrap_or(word);
+            match number.parse::<f64>() {
+                Ok(value) if value.is_finite() => number.to_string(),
+                _ => "null".to_string(),
+            }
+        }
+    }
+}
+
+/// Consume the characters up to `end` included
+fn skip_until(chars: &mut Peekable<Chars>, end: char) {
+    for c in chars.by_ref() {
+        if c == end {
+            break;
+        }
+    }
+}
+
+/// Read a JavaScript string literal, the quote being the next character
+fn read_string(chars: &mut Peekable<Chars>) -> String {
+    let quote = chars.next().expect("a string starts with a quote");
+    let mut string = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some('r') => string.push('\r'),
+                Some('t') => string.push('\t'),
+                Some('b') => string.push('\u{8}'),
+                Some('f') => string.push('\u{c}'),
+                Some('v') => string.push('\u{b}'),
+                Some('0') => string.push('\0'),
+                Some('x') => push_code(&mut string, chars.by_ref().take(2).collect::<String>()),
+                Some('u') if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    let code: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                    push_code(&mut string, code);
+                }
+                Some('u') => push_code(&mut string, chars.by_ref().take(4).collect::<String>()),
+                Some(other) => string.push(other),
+                None => break,
+            },
+            c if c == quote => break,
+            c => string.push(c),
+        }
+    }
+    string
+}
+
+fn push_code(string: &mut String, code: String) {
+    match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+        Some(c) => string.push(c),
+        None => string.push(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = concat!(
+        "\x1b[35m[2024-07-05T12:06:08.501Z]  INFO: Requesting https://www.rubensworks.net/\x1b[0m { headers: { accept: 'text/turtle' }, method: 'GET', actor: 'urn:comunica:default:http/actors#fetch' }\n",
+        "[2024-07-05T12:06:08.654Z]  TRACE: Link queue changed {\n",
+        "  data: {\n",
+        "    type: 'push',\n",
+        "    link: {\n",
+        "      url: 'https://www.rubensworks.net/b',\n",
+        "      producedByActor: { name: 'urn:comunica:default:extract-links/actors#predicates-solid', metadata: [Object] },\n",
+        "      timestamp: 2912.4356,\n",
+        "    },\n",
+        "    query: 'SELECT * WHERE {\\n' +\n",
+        "      ' ?s ?p ?o }',\n",
+        "    queue: { size: 1, push: { 'urn:comunica:default:extract-links/actors#predicates-solid': 1 }, pop: {} }\n",
+        "  },\n",
+        "  extra: [ 'it\\'s', undefined, ... 2 more items ]\n",
+        "}\n",
+        "[2024-07-05T12:06:08.655Z]  TRACE: Link queue changed { data: { type: 'pop'\n",
+    );
+
+    fn records() -> Vec<PrettyRecord> {
+        let mut records = PrettyRecords::default();
+        let mut completed = Vec::new();
+        for (index, line) in LOG.lines().enumerate() {
+            completed.extend(records.push(index + 1, line));
+        }
+        completed.extend(records.finish());
+        completed
+    }
+
+    #[test]
+    fn should_detect_the_header_of_a_record() {
+        assert!(is_header(
+            "\x1b[35m[2024-07-05T12:06:08.654Z]  TRACE: Link queue changed\x1b[0m {"
+        ));
+        assert!(!is_header(r#"{"msg":"Link queue changed"}"#));
+        assert!(!is_header("  data: {"));
+    }
+
+    #[test]
+    fn should_gather_the_lines_of_the_records() {
+        let records = records();
+
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.line, record.message.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, "Requesting https://www.rubensworks.net/"),
+                (2, "Link queue changed"),
+                (16, "Link queue changed"),
+            ]
+        );
+        assert_eq!(records[1].data.lines().count(), 14);
+    }
+
+    #[test]
+    fn should_convert_the_inspected_data() {
+        let records = records();
+
+        let request = records[0].bunyan().unwrap();
+        assert_eq!(request["actor"], "urn:comunica:default:http/actors#fetch");
+        assert_eq!(request["level"], 30);
+        let event = records[1].bunyan().unwrap();
+        assert_eq!(event["msg"], "Link queue changed");
+        assert_eq!(event["time"], "2024-07-05T12:06:08.654Z");
+        assert_eq!(event["level"], 10);
+        assert_eq!(event["data"]["query"], "SELECT * WHERE {\n ?s ?p ?o }");
+        assert_eq!(event["data"]["link"]["timestamp"], 2912.4356);
+        assert_eq!(
+            event["data"]["link"]["producedByActor"]["metadata"],
+            Value::Null
+        );
+        assert_eq!(event["extra"], serde_json::json!(["it's", null]));
+        assert!(matches!(
+            records[2].bunyan(),
+            Err(ParseErrorKind::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn should_keep_json_data() {
+        let json = r#"{"data":{"type":"push","size":-1.5e3,"ok":true,"none":null,"list":[1,"a"]}}"#;
+
+        let value: Value = serde_json::from_str(&inspected_to_json(json)).unwrap();
+
+        assert_eq!(value, serde_json::from_str::<Value>(json).unwrap());
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::util::one_line;
+
+/// Number of hexadecimal digits of a query id
+const ID_LENGTH: usize = 12;
+
+/// Stable identifier of a query, the first digits of the SHA-256 of its one-line text
+pub fn query_id(query: &str) -> String {
+    let digest = Sha256::digest(query.as_bytes());
+    let mut id = String::with_capacity(ID_LENGTH);
+    for byte in digest.iter().take(ID_LENGTH / 2) {
+        id.push_str(&format!("{byte:02x}"));
+    }
+    id
+}
+
+/// Names given by the user to the queries, keyed by query id or by query text
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QueryNames(HashMap<String, String>);
+
+impl QueryNames {
+    pub fn new(names: HashMap<String, String>) -> Self {
+        // a query text is matched once its line breaks are replaced, like the queries of the log
+        Self(
+            names
+                .into_iter()
+                .map(|(key, name)| (one_line(&key), name))
+                .collect(),
+        )
+    }
+
+    /// Read a JSON object mapping query ids or query texts to names
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let names: HashMap<String, String> = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(Self::new(names))
+    }
+
+    pub fn get(&self, query: &str) -> Option<&str> {
+        self.0
+            .get(&query_id(query))
+            .or_else(|| self.0.get(query))
+            .map(String::as_str)
+    }
+}
+
+/// A query of the query table
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct QueryEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub query: String,
+}
+
+/// The id, name and text of each distinct query, keyed by id in the order the queries are first seen
+pub fn query_table<'a>(
+    queries: impl IntoIterator<Item = &'a String>,
+    names: &QueryNames,
+) -> IndexMap<String, QueryEntry> {
+    queries
+        .into_iter()
+        .map(|query| {
+            let entry = QueryEntry {
+                name: names.get(query).map(str::to_string),
+                query: query.clone(),
+            };
+            (query_id(query), entry)
+        })
+        .collect()
+}
+
+/// Write the query table as CSV with an `id`, a `name` and a `query` column
+pub fn write_csv<W: Write>(table: &IndexMap<String, QueryEntry>, writer: W) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(["id", "name", "query"])?;
+    for (id, entry) in table {
+        writer.write_record([
+            id.as_str(),
+            entry.name.as_deref().unwrap_or_default(),
+            entry.query.as_str(),
+        ])?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUERY: &str = "SELECT * WHERE { ?s ?p ?o }";
+
+    #[test]
+    fn should_identify_a_query_by_its_hash() {
+        assert_eq!(query_id(QUERY), "dab3891081bc");
+        assert_eq!(query_id(QUERY).len(), ID_LENGTH);
+        assert_ne!(query_id(QUERY), query_id("ASK { ?s ?p ?o }"));
+    }
+
+    #[test]
+    fn should_name_the_queries_by_id_or_text() {
+        let names = QueryNames::new(HashMap::from([
+            (query_id(QUERY), "all".to_string()),
+            ("ASK {\n ?s ?p ?o\n}".to_string(), "any".to_string()),
+        ]));
+        let queries = [
+            QUERY.to_string(),
+            "ASK {  ?s ?p ?o }".to_string(),
+            "SELECT ?s WHERE { ?s ?p ?o }".to_string(),
+        ];
+
+        let table = query_table(&queries, &names);
+        let mut output = Vec::new();
+        write_csv(&table, &mut output).unwrap();
+
+        assert_eq!(names.get(QUERY), Some("all"));
+        assert_eq!(names.get("ASK {  ?s ?p ?o }"), Some("any"));
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[&query_id(QUERY)].name.as_deref(), Some("all"));
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().next(), Some("id,name,query"));
+        assert!(output.contains(&format!("{},all,{QUERY}", query_id(QUERY))));
+    }
+}
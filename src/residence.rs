@@ -0,0 +1,294 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use serde::Serialize;
+
+use crate::object::{EventType, History, LinkEvent};
+
+/// Name used by comunica for the links that were not produced by an actor
+pub const UNKNOWN_ACTOR: &str = "unknown";
+
+/// The time spent by a link in the queue, between its push and its pop
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkResidence {
+    pub url: String,
+    pub actor: String,
+    pub pushed_at: f64,
+    pub popped_at: f64,
+    pub waiting_time: f64,
+}
+
+/// A link pushed into the queue that was never popped
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingLink {
+    pub url: String,
+    pub actor: String,
+    pub pushed_at: f64,
+}
+
+/// Distribution of a set of durations in milliseconds, using the nearest-rank method
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DurationStats {
+    pub count: usize,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl DurationStats {
+    /// Compute the distribution of `durations`, returns `None` when it is empty
+    pub fn from_durations(mut durations: Vec<f64>) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let rank = (p / 100.0 * durations.len() as f64).ceil() as usize;
+            durations[rank.max(1) - 1]
+        };
+        Some(Self {
+            count: durations.len(),
+            p50: percentile(50.0),
+            p90: percentile(90.0),
+            p99: percentile(99.0),
+            max: durations[durations.len() - 1],
+        })
+    }
+}
+
+/// Residence time analysis of the links of a query
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResidenceReport {
+    pub links: Vec<LinkResidence>,
+    /// The pushes left in the queue, in log order
+    pub never_popped: Vec<PendingLink>,
+    /// Number of pops that do not match a previous push of the same url
+    pub unmatched_pops: usize,
+    pub waiting_time: Option<DurationStats>,
+    pub waiting_time_by_actor: BTreeMap<String, DurationStats>,
+}
+
+pub(crate) fn actor_name(event: &LinkEvent) -> String {
+    event
+        .link
+        .produced_by_actor
+        .as_ref()
+        .map(|actor| actor.name.clone())
+        .unwrap_or_else(|| UNKNOWN_ACTOR.to_string())
+}
+
+/// The pushes and pops of a query paired by url, see [`pair`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pairs<'a> {
+    /// Every pop in log order with the push it removed from the queue, `None` when no push of its
+    /// url is pending when it is logged
+    pub pops: Vec<(Option<&'a LinkEvent>, &'a LinkEvent)>,
+    /// The pushes left in the queue, in log order
+    pub never_popped: Vec<&'a LinkEvent>,
+}
+
+/// Walk the events of a query in log order and pair every pop with the earliest push of the same
+/// url logged before it and not popped yet
+pub fn pair(history: &History) -> Pairs<'_> {
+    // the pushes waiting for their pop, with their position among the pushes
+    let mut pending: HashMap<&str, VecDeque<(usize, &LinkEvent)>> = HashMap::new();
+    let mut pops = Vec::new();
+    for (position, (event_type, event)) in history.events().into_iter().enumerate() {
+        let queue = pending.entry(&event.link.url).or_default();
+        match event_type {
+            EventType::Push => queue.push_back((position, event)),
+            EventType::Pop => pops.push((queue.pop_front().map(|(_, push)| push), event)),
+        }
+    }
+
+    let mut never_popped: Vec<(usize, &LinkEvent)> = pending.into_values().flatten().collect();
+    never_popped.sort_by_key(|(position, _)| *position);
+    Pairs {
+        pops,
+        never_popped: never_popped.into_iter().map(|(_, push)| push).collect(),
+    }
+}
+
+/// Pair every pop of a query with the earliest pending push of the same url, see [`pair`]
+pub fn residence(history: &History) -> ResidenceReport {
+    let pairs = pair(history);
+    let mut links = Vec::new();
+    let mut unmatched_pops = 0;
+    for (push, pop) in pairs.pops {
+        match push {
+            Some(push) => links.push(LinkResidence {
+                url: push.link.url.clone(),
+                actor: actor_name(push),
+                pushed_at: push.link.timestamp,
+                popped_at: pop.link.timestamp,
+                waiting_time: pop.link.timestamp - push.link.timestamp,
+            }),
+            None => unmatched_pops += 1,
+        }
+    }
+
+    let never_popped = pairs
+        .never_popped
+        .into_iter()
+        .map(|push| PendingLink {
+            url: push.link.url.clone(),
+            actor: actor_name(push),
+            pushed_at: push.link.timestamp,
+        })
+        .collect();
+
+    let mut waiting_times_by_actor: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for link in &links {
+        waiting_times_by_actor
+            .entry(link.actor.clone())
+            .or_default()
+            .push(link.waiting_time);
+    }
+
+    ResidenceReport {
+        waiting_time: DurationStats::from_durations(
+            links.iter().map(|link| link.waiting_time).collect(),
+        ),
+        waiting_time_by_actor: waiting_times_by_actor
+            .into_iter()
+            .filter_map(|(actor, waiting_times)| {
+                DurationStats::from_durations(waiting_times).map(|stats| (actor, stats))
+            })
+            .collect(),
+        links,
+        never_popped,
+        unmatched_pops,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_reader;
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://www.rubensworks.net/a","timestamp":1.0,"producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://www.rubensworks.net/b","timestamp":2.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":2,"push":{"actor-a":1,"unknown":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://www.rubensworks.net/c","timestamp":3.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":3,"push":{"actor-a":1,"unknown":2},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://www.rubensworks.net/a","timestamp":5.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":2,"push":{"actor-a":1,"unknown":2},"pop":{"actor-a":1}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://www.rubensworks.net/b","timestamp":6.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1,"unknown":2},"pop":{"actor-a":1,"unknown":1}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://www.rubensworks.net/z","timestamp":7.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":0,"push":{"actor-a":1,"unknown":2},"pop":{"actor-a":1,"unknown":2}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn should_pair_pushes_and_pops() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+
+        let report = residence(history.values().next().unwrap());
+
+        assert_eq!(
+            report.links,
+            vec![
+                LinkResidence {
+                    url: "https://www.rubensworks.net/a".to_string(),
+                    actor: "actor-a".to_string(),
+                    pushed_at: 1.0,
+                    popped_at: 5.0,
+                    waiting_time: 4.0,
+                },
+                LinkResidence {
+                    url: "https://www.rubensworks.net/b".to_string(),
+                    actor: UNKNOWN_ACTOR.to_string(),
+                    pushed_at: 2.0,
+                    popped_at: 6.0,
+                    waiting_time: 4.0,
+                },
+            ]
+        );
+        assert_eq!(
+            report.never_popped,
+            vec![PendingLink {
+                url: "https://www.rubensworks.net/c".to_string(),
+                actor: UNKNOWN_ACTOR.to_string(),
+                pushed_at: 3.0,
+            }]
+        );
+        assert_eq!(report.unmatched_pops, 1);
+        assert_eq!(report.waiting_time.as_ref().unwrap().count, 2);
+        assert_eq!(
+            report.waiting_time_by_actor.keys().collect::<Vec<_>>(),
+            vec!["actor-a", UNKNOWN_ACTOR]
+        );
+    }
+
+    #[test]
+    fn should_list_the_links_never_popped_in_log_order() {
+        let log: String = ["c", "a", "b", "a"]
+            .iter()
+            .map(|name| {
+                format!(
+                    r#"{{"data":{{"type":"push","link":{{"url":"https://www.rubensworks.net/{name}","timestamp":1.0}},"query":"SELECT * WHERE {{ ?s ?p ?o }}","queue":{{"size":1,"push":{{}},"pop":{{}}}}}},"msg":"Link queue changed"}}{}"#,
+                    "\n"
+                )
+            })
+            .collect();
+        let history = parse_reader(log.as_bytes()).unwrap();
+
+        let report = residence(history.values().next().unwrap());
+
+        let urls: Vec<&str> = report
+            .never_popped
+            .iter()
+            .map(|link| link.url.as_str())
+            .collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://www.rubensworks.net/c",
+                "https://www.rubensworks.net/a",
+                "https://www.rubensworks.net/b",
+                "https://www.rubensworks.net/a",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_not_pair_a_pop_with_a_later_push() {
+        let log = concat!(
+            r#"{"data":{"type":"pop","link":{"url":"https://www.rubensworks.net/a","timestamp":1.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":0,"push":{},"pop":{"unknown":1}}},"msg":"Link queue changed"}"#,
+            "\n",
+            r#"{"data":{"type":"push","link":{"url":"https://www.rubensworks.net/a","timestamp":9.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"unknown":1},"pop":{"unknown":1}}},"msg":"Link queue changed"}"#,
+            "\n",
+        );
+        let history = parse_reader(log.as_bytes()).unwrap();
+
+        let report = residence(history.values().next().unwrap());
+
+        assert!(report.links.is_empty());
+        assert_eq!(report.unmatched_pops, 1);
+        assert_eq!(report.never_popped[0].pushed_at, 9.0);
+    }
+
+    #[test]
+    fn should_compute_nearest_rank_percentiles() {
+        let durations: Vec<f64> = (1..=100).rev().map(f64::from).collect();
+
+        let stats = DurationStats::from_durations(durations).unwrap();
+
+        assert_eq!(
+            stats,
+            DurationStats {
+                count: 100,
+                p50: 50.0,
+                p90: 90.0,
+                p99: 99.0,
+                max: 100.0,
+            }
+        );
+        assert_eq!(DurationStats::from_durations(Vec::new()), None);
+    }
+}
@@ -0,0 +1,84 @@
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::object::{EventType, History, HistoryKey, LinkEvent, QueryEvent, SourceEvent};
+use crate::query::query_id;
+
+/// The history of every query of one log
+#[derive(Debug, Default, PartialEq)]
+pub struct Run {
+    /// Identifier of the run, `None` when a single unlabelled log is parsed
+    pub label: Option<String>,
+    pub history: IndexMap<HistoryKey, History>,
+    /// Requests and identifications of the sources, only read for the analyses that need them
+    pub sources: Vec<SourceEvent>,
+}
+
+impl Run {
+    pub fn new(label: Option<String>, history: IndexMap<HistoryKey, History>) -> Self {
+        Self {
+            label,
+            history,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Name of an execution of a query of this run, prefixed by the label of the run if there is one
+    pub fn name(&self, key: &HistoryKey) -> String {
+        match &self.label {
+            Some(label) => format!("{label}: {key}"),
+            None => key.to_string(),
+        }
+    }
+}
+
+/// A link queue event tagged with the run it belongs to, its query is identified by [`query_id`]
+#[derive(Debug, Serialize)]
+pub struct RunEvent<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<&'a str>,
+    pub query_id: String,
+    pub execution: usize,
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+    #[serde(flatten)]
+    pub event: &'a LinkEvent,
+}
+
+impl<'a> RunEvent<'a> {
+    pub fn new(run: Option<&'a str>, event: &'a QueryEvent) -> Self {
+        Self {
+            run,
+            query_id: query_id(&event.query),
+            execution: event.execution,
+            event_type: event.event_type,
+            event: &event.event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_line;
+
+    #[test]
+    fn should_identify_the_query_of_an_event() {
+        let line = r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":1.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed"}"#;
+        let event = parse_line(line).unwrap().unwrap();
+
+        let value = serde_json::to_value(RunEvent::new(Some("run-1"), &event)).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "run": "run-1",
+                "query_id": "dab3891081bc",
+                "execution": 1,
+                "type": "push",
+                "link": {"url": "https://example.org/a", "timestamp": 1.0},
+                "queue": {"size": 1, "push": {}, "pop": {}},
+            })
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, Transaction};
+
+use crate::query::query_id;
+use crate::run::Run;
+
+/// Tables of the database, created when they do not exist so that runs accumulate in the same file
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    label TEXT,
+    -- seconds since the Unix epoch
+    imported_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS queries (
+    id INTEGER PRIMARY KEY,
+    text TEXT NOT NULL UNIQUE,
+    -- id of the query in the other outputs, a prefix of the SHA-256 of the text
+    query_id TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS links (
+    id INTEGER PRIMARY KEY,
+    url TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS actors (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+CREATE TABLE IF NOT EXISTS events (
+    id INTEGER PRIMARY KEY,
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    query_id INTEGER NOT NULL REFERENCES queries(id),
+    -- number of the execution of the query in the run, starting at 1
+    execution INTEGER NOT NULL,
+    -- position of the event in the log order of the execution of the query
+    seq INTEGER NOT NULL,
+    type TEXT NOT NULL CHECK (type IN ('push', 'pop')),
+    link_id INTEGER NOT NULL REFERENCES links(id),
+    parent_id INTEGER REFERENCES links(id),
+    -- actor that produced the link, NULL when comunica does not report it
+    actor_id INTEGER REFERENCES actors(id),
+    timestamp REAL NOT NULL,
+    time TEXT,
+    queue_size INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS counters (
+    event_id INTEGER NOT NULL REFERENCES events(id),
+    actor_id INTEGER NOT NULL REFERENCES actors(id),
+    push INTEGER NOT NULL,
+    pop INTEGER NOT NULL,
+    PRIMARY KEY (event_id, actor_id)
+) WITHOUT ROWID;
+CREATE INDEX IF NOT EXISTS events_by_run_query ON events (run_id, query_id, seq);
+CREATE INDEX IF NOT EXISTS events_by_query ON events (query_id);
+CREATE INDEX IF NOT EXISTS events_by_link ON events (link_id);
+CREATE INDEX IF NOT EXISTS events_by_parent ON events (parent_id);
+CREATE INDEX IF NOT EXISTS events_by_actor ON events (actor_id);
+CREATE INDEX IF NOT EXISTS counters_by_actor ON counters (actor_id);
+";
+
+/// Identifiers of the values of a table with a unique text column, cached for the export
+struct Interned {
+    insert: &'static str,
+    select: &'static str,
+    /// Computes the second value of the insert from the text, for the tables with a derived column
+    derived: Option<fn(&str) -> String>,
+    ids: HashMap<String, i64>,
+}
+
+impl Interned {
+    fn new(insert: &'static str, select: &'static str) -> Self {
+        Self {
+            insert,
+            select,
+            derived: None,
+            ids: HashMap::new(),
+        }
+    }
+
+    fn with_derived(mut self, derived: fn(&str) -> String) -> Self {
+        self.derived = Some(derived);
+        self
+    }
+
+    fn id(&mut self, transaction: &Transaction, value: &str) -> rusqlite::Result<i64> {
+        if let Some(id) = self.ids.get(value) {
+            return Ok(*id);
+        }
+        let mut insert = transaction.prepare_cached(self.insert)?;
+        match self.derived {
+            Some(derived) => insert.execute(params![value, derived(value)])?,
+            None => insert.execute(params![value])?,
+        };
+        let id = transaction
+            .prepare_cached(self.select)?
+            .query_row(params![value], |row| row.get(0))?;
+        self.ids.insert(value.to_string(), id);
+        Ok(id)
+    }
+}
+
+/// Append the runs to the database, in a single transaction
+pub fn export(runs: &[Run], connection: &mut Connection) -> rusqlite::Result<()> {
+    connection.execute_batch(SCHEMA)?;
+    let transaction = connection.transaction()?;
+    let mut queries = Interned::new(
+        "INSERT OR IGNORE INTO queries (text, query_id) VALUES (?1, ?2)",
+        "SELECT id FROM queries WHERE text = ?1",
+    )
+    .with_derived(query_id);
+    let mut links = Interned::new(
+        "INSERT OR IGNORE INTO links (url) VALUES (?1)",
+        "SELECT id FROM links WHERE url = ?1",
+    );
+    let mut actors = Interned::new(
+        "INSERT OR IGNORE INTO actors (name) VALUES (?1)",
+        "SELECT id FROM actors WHERE name = ?1",
+    );
+    let imported_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+
+    for run in runs {
+        transaction.execute(
+            "INSERT INTO runs (label, imported_at) VALUES (?1, ?2)",
+            params![run.label, imported_at],
+        )?;
+        let run_id = transaction.last_insert_rowid();
+        for (key, history) in &run.history {
+            let query_id = queries.id(&transaction, &key.query)?;
+            for (seq, (event_type, event)) in history.events().into_iter().enumerate() {
+                let link_id = links.id(&transaction, &event.link.url)?;
+                let parent_id = match &event.link.parent {
+                    Some(parent) => Some(links.id(&transaction, parent)?),
+                    None => None,
+                };
+                let actor_id = match &event.link.produced_by_actor {
+                    Some(actor) => Some(actors.id(&transaction, &actor.name)?),
+                    None => None,
+                };
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO events (run_id, query_id, execution, seq, type, link_id, parent_id, actor_id, timestamp, time, queue_size)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                    )?
+                    .execute(params![
+                        run_id,
+                        query_id,
+                        key.execution as i64,
+                        seq as i64,
+                        event_type.as_str(),
+                        link_id,
+                        parent_id,
+                        actor_id,
+                        event.link.timestamp,
+                        event.envelope.time,
+                        event.queue.size as i64,
+                    ])?;
+                let event_id = transaction.last_insert_rowid();
+
+                let mut counters: HashMap<&str, (u64, u64)> = HashMap::new();
+                for (actor, count) in &event.queue.push {
+                    counters.entry(actor).or_default().0 = *count;
+                }
+                for (actor, count) in &event.queue.pop {
+                    counters.entry(actor).or_default().1 = *count;
+                }
+                for (actor, (push, pop)) in counters {
+                    let actor_id = actors.id(&transaction, actor)?;
+                    transaction
+                        .prepare_cached(
+                            "INSERT INTO counters (event_id, actor_id, push, pop) VALUES (?1, ?2, ?3, ?4)",
+                        )?
+                        .execute(params![event_id, actor_id, push as i64, pop as i64])?;
+                }
+            }
+        }
+    }
+    transaction.commit()
+}
+
+/// Append the runs to the SQLite database at `path`, which is created if it does not exist
+pub fn write_sqlite<P: AsRef<Path>>(runs: &[Run], path: P) -> io::Result<()> {
+    let mut connection = Connection::open(path).map_err(io::Error::other)?;
+    export(runs, &mut connection).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_reader;
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":1.0,"producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1},"pop":{}}},"msg":"Link queue changed","time":"2024-08-07T08:21:44.100Z"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/b","timestamp":2.0,"parent":"https://example.org/a"},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":2,"push":{"actor-a":1,"unknown":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"pop","link":{"url":"https://example.org/a","timestamp":3.0,"producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1,"unknown":1},"pop":{"actor-a":1}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    fn count(connection: &Connection, table: &str) -> i64 {
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn should_append_runs_to_the_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        let run = |label: &str| {
+            Run::new(
+                Some(label.to_string()),
+                parse_reader(LOG.as_bytes()).unwrap(),
+            )
+        };
+
+        export(&[run("run-1")], &mut connection).unwrap();
+        export(&[run("run-2")], &mut connection).unwrap();
+
+        assert_eq!(count(&connection, "runs"), 2);
+        assert_eq!(count(&connection, "queries"), 1);
+        let id: String = connection
+            .query_row("SELECT query_id FROM queries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(id, "dab3891081bc");
+        assert_eq!(count(&connection, "links"), 2);
+        assert_eq!(count(&connection, "actors"), 2);
+        assert_eq!(count(&connection, "events"), 6);
+        assert_eq!(count(&connection, "counters"), 10);
+        let popped: Vec<(String, String, i64, i64)> = connection
+            .prepare(
+                "SELECT runs.label, links.url, events.seq, counters.pop
+                 FROM events
+                 JOIN runs ON runs.id = events.run_id
+                 JOIN links ON links.id = events.link_id
+                 JOIN counters ON counters.event_id = events.id
+                 JOIN actors ON actors.id = counters.actor_id
+                 WHERE events.type = 'pop' AND actors.name = 'actor-a'
+                 ORDER BY runs.label",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            popped,
+            vec![
+                (
+                    "run-1".to_string(),
+                    "https://example.org/a".to_string(),
+                    2,
+                    1
+                ),
+                (
+                    "run-2".to_string(),
+                    "https://example.org/a".to_string(),
+                    2,
+                    1
+                ),
+            ]
+        );
+        let parent: Option<String> = connection
+            .query_row(
+                "SELECT parents.url FROM events JOIN links ON links.id = events.link_id
+                 JOIN links AS parents ON parents.id = events.parent_id
+                 WHERE links.url = 'https://example.org/b' LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(parent.as_deref(), Some("https://example.org/a"));
+    }
+
+    #[test]
+    fn should_keep_the_executions_of_a_query_apart() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        let run = Run::new(None, parse_reader(LOG.repeat(2).as_bytes()).unwrap());
+
+        export(&[run], &mut connection).unwrap();
+
+        assert_eq!(count(&connection, "queries"), 1);
+        let executions: Vec<(i64, i64)> = connection
+            .prepare("SELECT execution, COUNT(*) FROM events GROUP BY execution ORDER BY execution")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(executions, vec![(1, 3), (2, 3)]);
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::object::{EventType, History};
+use crate::query::query_id;
+use crate::run::Run;
+
+/// The state of the link queue right after an event
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct OccupancyPoint<'a> {
+    pub timestamp: f64,
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+    pub url: &'a str,
+    pub size: u64,
+    pub push: &'a BTreeMap<String, u64>,
+    pub pop: &'a BTreeMap<String, u64>,
+}
+
+/// The state of the link queue after each event of a query, in log order
+pub fn occupancy(history: &History) -> Vec<OccupancyPoint<'_>> {
+    history
+        .events()
+        .into_iter()
+        .map(|(event_type, event)| OccupancyPoint {
+            timestamp: event.link.timestamp,
+            event_type,
+            url: &event.link.url,
+            size: event.queue.size,
+            push: &event.queue.push,
+            pop: &event.queue.pop,
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct QueryOccupancyPoint<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<&'a str>,
+    query_id: String,
+    execution: usize,
+    #[serde(flatten)]
+    point: &'a OccupancyPoint<'a>,
+}
+
+/// Write the occupancy series of every query as newline delimited JSON, the queries are identified
+/// by [`query_id`] and the points of labelled runs have a `run` field
+pub fn write_ndjson<W: Write>(runs: &[Run], mut writer: W) -> io::Result<()> {
+    for run in runs {
+        for (key, query_history) in &run.history {
+            for point in occupancy(query_history) {
+                serde_json::to_writer(
+                    &mut writer,
+                    &QueryOccupancyPoint {
+                        run: run.label.as_deref(),
+                        query_id: query_id(&key.query),
+                        execution: key.execution,
+                        point: &point,
+                    },
+                )?;
+                writer.write_all(b"\n")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The `push:<actor>` and `pop:<actor>` columns of the CSV exports, one of each per producing actor
+/// found in the counters of the runs
+pub(crate) struct CounterColumns<'a> {
+    actors: BTreeSet<&'a str>,
+}
+
+impl<'a> CounterColumns<'a> {
+    pub(crate) fn new(runs: &'a [Run]) -> Self {
+        let actors = runs
+            .iter()
+            .flat_map(|run| run.history.values())
+            .flat_map(|query_history| query_history.push.iter().chain(&query_history.pop))
+            .flat_map(|event| event.queue.push.keys().chain(event.queue.pop.keys()))
+            .map(String::as_str)
+            .collect();
+        Self { actors }
+    }
+
+    pub(crate) fn header(&self) -> impl Iterator<Item = String> + '_ {
+        let push = self.actors.iter().map(|actor| format!("push:{actor}"));
+        let pop = self.actors.iter().map(|actor| format!("pop:{actor}"));
+        push.chain(pop)
+    }
+
+    /// The values of the columns for the counters of an event, 0 for the actors it does not count
+    pub(crate) fn values<'b>(
+        &'b self,
+        push: &'b BTreeMap<String, u64>,
+        pop: &'b BTreeMap<String, u64>,
+    ) -> impl Iterator<Item = String> + 'b {
+        [push, pop].into_iter().flat_map(move |counters| {
+            self.actors
+                .iter()
+                .map(|actor| counters.get(*actor).copied().unwrap_or(0).to_string())
+        })
+    }
+}
+
+/// Write the occupancy series of every query as CSV with one `push` and one `pop` column
+/// per producing actor found in the runs, and a leading `run` column when a run is labelled.
+/// The queries are identified by [`query_id`].
+pub fn write_csv<W: Write>(runs: &[Run], writer: W) -> io::Result<()> {
+    let labelled = runs.iter().any(|run| run.label.is_some());
+    let counters = CounterColumns::new(runs);
+
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut header = Vec::new();
+    if labelled {
+        header.push("run".to_string());
+    }
+    header.extend([
+        "query_id".to_string(),
+        "execution".to_string(),
+        "timestamp".to_string(),
+        "type".to_string(),
+        "url".to_string(),
+        "size".to_string(),
+    ]);
+    header.extend(counters.header());
+    writer.write_record(&header)?;
+
+    for run in runs {
+        for (key, query_history) in &run.history {
+            for point in occupancy(query_history) {
+                let mut record = Vec::new();
+                if labelled {
+                    record.push(run.label.clone().unwrap_or_default());
+                }
+                record.extend([
+                    query_id(&key.query),
+                    key.execution.to_string(),
+                    point.timestamp.to_string(),
+                    point.event_type.as_str().to_string(),
+                    point.url.to_string(),
+                    point.size.to_string(),
+                ]);
+                record.extend(counters.values(point.push, point.pop));
+                writer.write_record(&record)?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_reader;
+
+    const LOG: &str = concat!(
+        r#"{"level":10,"data":{"type":"push","link":{"url":"https://www.rubensworks.net/a","timestamp":1.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"level":10,"data":{"type":"push","link":{"url":"https://www.rubensworks.net/b","timestamp":2.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":2,"push":{"actor-a":1,"actor-b":1},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"level":10,"data":{"type":"pop","link":{"url":"https://www.rubensworks.net/a","timestamp":3.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{"actor-a":1,"actor-b":1},"pop":{"actor-a":1}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn should_merge_push_and_pop_in_log_order() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+        let series = occupancy(history.values().next().unwrap());
+
+        let summary: Vec<(f64, EventType, &str, u64)> = series
+            .iter()
+            .map(|point| (point.timestamp, point.event_type, point.url, point.size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1.0, EventType::Push, "https://www.rubensworks.net/a", 1),
+                (2.0, EventType::Push, "https://www.rubensworks.net/b", 2),
+                (3.0, EventType::Pop, "https://www.rubensworks.net/a", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_write_csv() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+        let mut output = Vec::new();
+
+        write_csv(&[Run::new(None, history)], &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "query_id,execution,timestamp,type,url,size,push:actor-a,push:actor-b,pop:actor-a,pop:actor-b",
+                "dab3891081bc,1,1,push,https://www.rubensworks.net/a,1,1,0,0,0",
+                "dab3891081bc,1,2,push,https://www.rubensworks.net/b,2,1,1,0,0",
+                "dab3891081bc,1,3,pop,https://www.rubensworks.net/a,1,1,1,1,0",
+            ]
+        );
+    }
+
+    #[test]
+    fn should_write_ndjson() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+        let mut output = Vec::new();
+
+        write_ndjson(&[Run::new(None, history)], &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let first: serde_json::Value =
+            serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        assert_eq!(output.lines().count(), 3);
+        assert_eq!(
+            first,
+            serde_json::json!({
+                "query_id": "dab3891081bc",
+                "execution": 1,
+                "timestamp": 1.0,
+                "type": "push",
+                "url": "https://www.rubensworks.net/a",
+                "size": 1,
+                "push": {"actor-a": 1},
+                "pop": {},
+            })
+        );
+    }
+
+    #[test]
+    fn should_label_the_runs() {
+        let runs = [
+            Run::new(
+                Some("run-1".to_string()),
+                parse_reader(LOG.as_bytes()).unwrap(),
+            ),
+            Run::new(
+                Some("run-2".to_string()),
+                parse_reader(LOG.as_bytes()).unwrap(),
+            ),
+        ];
+        let mut csv = Vec::new();
+        let mut ndjson = Vec::new();
+
+        write_csv(&runs, &mut csv).unwrap();
+        write_ndjson(&runs, &mut ndjson).unwrap();
+
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].starts_with("run,query_id,execution,timestamp"));
+        assert!(lines[1].starts_with("run-1,"));
+        assert!(lines[6].starts_with("run-2,"));
+        let ndjson = String::from_utf8(ndjson).unwrap();
+        let last: serde_json::Value = serde_json::from_str(ndjson.lines().last().unwrap()).unwrap();
+        assert_eq!(last["run"], "run-2");
+    }
+}
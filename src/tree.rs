@@ -0,0 +1,460 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::object::History;
+
+/// A document discovered during the traversal
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub url: String,
+    /// Index of the document in which the link was first discovered
+    pub parent: Option<usize>,
+    /// Name of the actor that produced the link
+    pub actor: Option<String>,
+    pub depth: usize,
+    pub children: Vec<usize>,
+}
+
+/// The tree explored by the link traversal of a query, rebuilt from the `link.parent` of the pushes.
+/// A link pushed from several documents is attached to the first one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraversalTree {
+    pub nodes: Vec<Node>,
+    index: HashMap<String, usize>,
+    /// Documents only known as the parent of a link, they are attached when their own push is seen
+    placeholders: HashSet<usize>,
+}
+
+impl TraversalTree {
+    pub fn from_history(history: &History) -> Self {
+        let mut tree = Self::default();
+        for push in &history.push {
+            let parent = push
+                .link
+                .parent
+                .as_ref()
+                .map(|parent| tree.placeholder(parent));
+            let actor = push
+                .link
+                .produced_by_actor
+                .as_ref()
+                .map(|actor| actor.name.clone());
+            tree.insert(&push.link.url, parent, actor);
+        }
+        tree
+    }
+
+    /// Add the parent of a link if it is not already in the tree, returns its index
+    fn placeholder(&mut self, url: &str) -> usize {
+        if let Some(index) = self.index.get(url) {
+            return *index;
+        }
+        let index = self.add(url, None, None);
+        self.placeholders.insert(index);
+        index
+    }
+
+    /// Add a pushed document, a document already added as the parent of a link is attached to
+    /// `parent` unless `parent` descends from it. Returns the index of the document.
+    fn insert(&mut self, url: &str, parent: Option<usize>, actor: Option<String>) -> usize {
+        let Some(index) = self.index.get(url).copied() else {
+            return self.add(url, parent, actor);
+        };
+        if self.placeholders.remove(&index) {
+            self.nodes[index].actor = actor;
+            if let Some(parent) =
+                parent.filter(|parent| !self.chain_indices(*parent).contains(&index))
+            {
+                self.nodes[index].parent = Some(parent);
+                self.nodes[parent].children.push(index);
+                self.update_depths(index);
+            }
+        }
+        index
+    }
+
+    fn add(&mut self, url: &str, parent: Option<usize>, actor: Option<String>) -> usize {
+        let index = self.nodes.len();
+        let depth = parent.map_or(0, |parent| self.nodes[parent].depth + 1);
+        self.nodes.push(Node {
+            url: url.to_string(),
+            parent,
+            actor,
+            depth,
+            children: Vec::new(),
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(index);
+        }
+        self.index.insert(url.to_string(), index);
+        index
+    }
+
+    /// Recompute the depth of the document at `index` and of its descendants from its parent
+    fn update_depths(&mut self, index: usize) {
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let depth = node.parent.map_or(0, |parent| self.nodes[parent].depth + 1);
+            self.nodes[index].depth = depth;
+            stack.extend(&self.nodes[index].children);
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<&Node> {
+        self.index.get(url).map(|index| &self.nodes[*index])
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.iter().filter(|node| node.parent.is_none())
+    }
+
+    pub fn max_depth(&self) -> usize {
+        self.nodes.iter().map(|node| node.depth).max().unwrap_or(0)
+    }
+
+    /// The indices of the documents from the document at `index` up to its root
+    fn chain_indices(&self, mut index: usize) -> Vec<usize> {
+        let mut chain = vec![index];
+        while let Some(parent) = self.nodes[index].parent {
+            chain.push(parent);
+            index = parent;
+        }
+        chain
+    }
+
+    /// The chain of urls from a root to the document at `index`
+    fn chain(&self, index: usize) -> Vec<String> {
+        let mut chain: Vec<String> = self
+            .chain_indices(index)
+            .into_iter()
+            .map(|index| self.nodes[index].url.clone())
+            .collect();
+        chain.reverse();
+        chain
+    }
+
+    /// The `count` deepest chains from a root to a leaf, deepest first
+    pub fn longest_chains(&self, count: usize) -> Vec<Vec<String>> {
+        let mut leaves: Vec<usize> = (0..self.nodes.len())
+            .filter(|index| self.nodes[*index].children.is_empty())
+            .collect();
+        leaves.sort_by(|a, b| self.nodes[*b].depth.cmp(&self.nodes[*a].depth));
+        leaves
+            .into_iter()
+            .take(count)
+            .map(|leaf| self.chain(leaf))
+            .collect()
+    }
+
+    pub fn summary(&self, chain_count: usize) -> TreeSummary {
+        let branching: Vec<usize> = self
+            .nodes
+            .iter()
+            .map(|node| node.children.len())
+            .filter(|children| *children > 0)
+            .collect();
+        TreeSummary {
+            documents: self.nodes.len(),
+            roots: self.roots().map(|node| node.url.clone()).collect(),
+            max_depth: self.max_depth(),
+            mean_branching_factor: if branching.is_empty() {
+                0.0
+            } else {
+                branching.iter().sum::<usize>() as f64 / branching.len() as f64
+            },
+            depth: self
+                .nodes
+                .iter()
+                .map(|node| (node.url.clone(), node.depth))
+                .collect(),
+            branching_factor: self
+                .nodes
+                .iter()
+                .filter(|node| !node.children.is_empty())
+                .map(|node| (node.url.clone(), node.children.len()))
+                .collect(),
+            longest_chains: self.longest_chains(chain_count),
+        }
+    }
+}
+
+/// Shape of the traversal tree of a query
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeSummary {
+    pub documents: usize,
+    pub roots: Vec<String>,
+    pub max_depth: usize,
+    /// Mean number of children of the documents with at least one child
+    pub mean_branching_factor: f64,
+    pub depth: BTreeMap<String, usize>,
+    /// Number of children of the documents with at least one child
+    pub branching_factor: BTreeMap<String, usize>,
+    pub longest_chains: Vec<Vec<String>>,
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Write the traversal tree of every query as Graphviz DOT, one `digraph` per query
+pub fn write_dot<'a, W: Write>(
+    trees: impl IntoIterator<Item = (&'a String, &'a TraversalTree)>,
+    mut writer: W,
+) -> io::Result<()> {
+    for (query, tree) in trees {
+        writeln!(writer, "digraph \"{}\" {{", escape_dot(query))?;
+        for node in &tree.nodes {
+            writeln!(
+                writer,
+                "  \"{}\" [depth={}];",
+                escape_dot(&node.url),
+                node.depth
+            )?;
+        }
+        for node in &tree.nodes {
+            if let Some(parent) = node.parent {
+                write!(
+                    writer,
+                    "  \"{}\" -> \"{}\"",
+                    escape_dot(&tree.nodes[parent].url),
+                    escape_dot(&node.url)
+                )?;
+                match &node.actor {
+                    Some(actor) => writeln!(writer, " [label=\"{}\"];", escape_dot(actor))?,
+                    None => writeln!(writer, ";")?,
+                }
+            }
+        }
+        writeln!(writer, "}}")?;
+    }
+    Ok(())
+}
+
+/// Write the traversal tree of every query as GraphML, one `graph` per query
+pub fn write_graphml<'a, W: Write>(
+    trees: impl IntoIterator<Item = (&'a String, &'a TraversalTree)>,
+    mut writer: W,
+) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="url" for="node" attr.name="url" attr.type="string"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="depth" for="node" attr.name="depth" attr.type="int"/>"#
+    )?;
+    writeln!(
+        writer,
+        r#"  <key id="actor" for="edge" attr.name="actor" attr.type="string"/>"#
+    )?;
+    for (graph_index, (query, tree)) in trees.into_iter().enumerate() {
+        let id = |node: usize| format!("g{graph_index}n{node}");
+        writeln!(
+            writer,
+            r#"  <graph id="g{graph_index}" edgedefault="directed">"#
+        )?;
+        writeln!(writer, "    <desc>{}</desc>", escape_xml(query))?;
+        for (index, node) in tree.nodes.iter().enumerate() {
+            writeln!(writer, r#"    <node id="{}">"#, id(index))?;
+            writeln!(
+                writer,
+                r#"      <data key="url">{}</data>"#,
+                escape_xml(&node.url)
+            )?;
+            writeln!(writer, r#"      <data key="depth">{}</data>"#, node.depth)?;
+            writeln!(writer, "    </node>")?;
+        }
+        for (index, node) in tree.nodes.iter().enumerate() {
+            if let Some(parent) = node.parent {
+                writeln!(
+                    writer,
+                    r#"    <edge source="{}" target="{}">"#,
+                    id(parent),
+                    id(index)
+                )?;
+                if let Some(actor) = &node.actor {
+                    writeln!(
+                        writer,
+                        r#"      <data key="actor">{}</data>"#,
+                        escape_xml(actor)
+                    )?;
+                }
+                writeln!(writer, "    </edge>")?;
+            }
+        }
+        writeln!(writer, "  </graph>")?;
+    }
+    writeln!(writer, "</graphml>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_reader;
+
+    const LOG: &str = concat!(
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":1.0,"parent":"https://example.org/","producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/b","timestamp":2.0,"parent":"https://example.org/"},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":2,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a/1","timestamp":3.0,"parent":"https://example.org/a"},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":3,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+        r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":4.0,"parent":"https://example.org/b"},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":4,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+        "\n",
+    );
+
+    #[test]
+    fn should_rebuild_the_tree() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+
+        let tree = TraversalTree::from_history(history.values().next().unwrap());
+
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(
+            tree.roots()
+                .map(|node| node.url.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://example.org/"]
+        );
+        assert_eq!(tree.get("https://example.org/a/1").unwrap().depth, 2);
+        assert_eq!(
+            tree.get("https://example.org/a").unwrap().actor.as_deref(),
+            Some("actor-a")
+        );
+        assert_eq!(tree.max_depth(), 2);
+        assert_eq!(
+            tree.longest_chains(1),
+            vec![vec![
+                "https://example.org/".to_string(),
+                "https://example.org/a".to_string(),
+                "https://example.org/a/1".to_string(),
+            ]]
+        );
+    }
+
+    #[test]
+    fn should_attach_a_parent_pushed_after_its_child() {
+        let log = concat!(
+            r#"{"data":{"type":"push","link":{"url":"https://example.org/a/1","timestamp":1.0,"parent":"https://example.org/a"},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+            "\n",
+            r#"{"data":{"type":"push","link":{"url":"https://example.org/a","timestamp":2.0,"parent":"https://example.org/","producedByActor":{"name":"actor-a"}},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":2,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+            "\n",
+            // the root is not moved below its own descendant
+            r#"{"data":{"type":"push","link":{"url":"https://example.org/","timestamp":3.0,"parent":"https://example.org/a/1"},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":3,"push":{},"pop":{}}},"msg":"Link queue changed"}"#,
+            "\n",
+        );
+        let history = parse_reader(log.as_bytes()).unwrap();
+
+        let tree = TraversalTree::from_history(history.values().next().unwrap());
+
+        let depths: Vec<(&str, usize)> = tree
+            .nodes
+            .iter()
+            .map(|node| (node.url.as_str(), node.depth))
+            .collect();
+        assert_eq!(
+            depths,
+            vec![
+                ("https://example.org/a", 1),
+                ("https://example.org/a/1", 2),
+                ("https://example.org/", 0),
+            ]
+        );
+        assert_eq!(
+            tree.get("https://example.org/a").unwrap().actor.as_deref(),
+            Some("actor-a")
+        );
+        assert_eq!(
+            tree.longest_chains(1),
+            vec![vec![
+                "https://example.org/".to_string(),
+                "https://example.org/a".to_string(),
+                "https://example.org/a/1".to_string(),
+            ]]
+        );
+    }
+
+    #[test]
+    fn should_summarize_the_tree() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+        let tree = TraversalTree::from_history(history.values().next().unwrap());
+
+        let summary = tree.summary(5);
+
+        assert_eq!(summary.documents, 4);
+        assert_eq!(summary.max_depth, 2);
+        assert_eq!(
+            summary.branching_factor,
+            BTreeMap::from([
+                ("https://example.org/".to_string(), 2),
+                ("https://example.org/a".to_string(), 1),
+            ])
+        );
+        assert_eq!(summary.mean_branching_factor, 1.5);
+        assert_eq!(summary.longest_chains.len(), 2);
+    }
+
+    #[test]
+    fn should_write_dot() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+        let trees: Vec<(&String, TraversalTree)> = history
+            .iter()
+            .map(|(key, query_history)| (&key.query, TraversalTree::from_history(query_history)))
+            .collect();
+        let mut output = Vec::new();
+
+        write_dot(
+            trees.iter().map(|(query, tree)| (*query, tree)),
+            &mut output,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("digraph \"SELECT * WHERE { ?s ?p ?o }\" {\n"));
+        assert!(output.contains(
+            "  \"https://example.org/\" -> \"https://example.org/a\" [label=\"actor-a\"];\n"
+        ));
+        assert!(output.contains("  \"https://example.org/a\" -> \"https://example.org/a/1\";\n"));
+        assert!(output.ends_with("}\n"));
+    }
+
+    #[test]
+    fn should_write_graphml() {
+        let history = parse_reader(LOG.as_bytes()).unwrap();
+        let trees: Vec<(&String, TraversalTree)> = history
+            .iter()
+            .map(|(key, query_history)| (&key.query, TraversalTree::from_history(query_history)))
+            .collect();
+        let mut output = Vec::new();
+
+        write_graphml(
+            trees.iter().map(|(query, tree)| (*query, tree)),
+            &mut output,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("<node ").count(), 4);
+        assert_eq!(output.matches("<edge ").count(), 3);
+        assert!(output.contains(r#"<edge source="g0n0" target="g0n1">"#));
+        assert!(output.ends_with("</graphml>\n"));
+    }
+}
@@ -1,55 +1,195 @@
 use std::collections::HashMap;
 
+use crate::error::ParseErrorKind;
 use crate::object::History;
 use lazy_static::lazy_static;
 use regex::Regex;
-use serde_json;
+use serde_json::{Map, Value};
 
-use crate::object::LinkEvent;
+use crate::object::{Envelope, EventType, LinkEvent, QueryEvent, SourceEvent, SourceEventType};
 
-/// convert a line into an [`HashMap<String, History>`] object
+/// convert a line into an [`HashMap<String, History>`] object,
+/// returns whether the line is a link queue event
 pub fn process_line(
-    line: &String,
+    line: &str,
     history: &mut HashMap<String, History>,
-) -> Result<(), serde_json::Error> {
-    lazy_static! {
-        static ref RE_EVENT: Regex =
-            Regex::new(r#".*?(?<jsonEvent>\{.+?"Link queue changed".*\})"#).unwrap();
-        static ref RE_QUERY: Regex = Regex::new(r"(\r\n|\n|\r)").unwrap();
+) -> Result<bool, ParseErrorKind> {
+    match parse_line(line)? {
+        Some(event) => {
+            history
+                .entry(event.query)
+                .or_default()
+                .add(event.event_type, event.event);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Message of the records of the link queue events
+pub const LINK_QUEUE_MESSAGE: &str = "Link queue changed";
+
+/// Start of the message of the records of the HTTP requests, followed by the requested url
+pub const REQUEST_MESSAGE: &str = "Requesting ";
+
+/// Start of the message of the records of the identified sources, followed by the url of the source
+pub const IDENTIFICATION_MESSAGE: &str = "Identified as file source: ";
+
+/// convert a line into a [`QueryEvent`], returns `None` when the line is not a link queue event
+pub fn parse_line(line: &str) -> Result<Option<QueryEvent>, ParseErrorKind> {
+    if !line.contains(LINK_QUEUE_MESSAGE) {
+        // Bunyan writes the message after the data, so a record cut off in its data has no message
+        let line = line.trim();
+        if line.starts_with("{\"") && !line.contains("\"msg\":") {
+            serde_json::from_str::<Map<String, Value>>(line)
+                .map_err(ParseErrorKind::InvalidJson)?;
+        }
+        return Ok(None);
+    }
+    match record(line, |message| message == LINK_QUEUE_MESSAGE)? {
+        Some(record) => parse_record(&record).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// convert a line into a [`SourceEvent`], returns `None` when the line is not the request or the
+/// identification of a source, or cannot be parsed
+pub fn parse_source_line(line: &str) -> Option<SourceEvent> {
+    if !line.contains(REQUEST_MESSAGE) && !line.contains(IDENTIFICATION_MESSAGE) {
+        return None;
+    }
+    record(line, |message| source_message(message).is_some())
+        .ok()
+        .flatten()
+        .and_then(|record| parse_source_record(&record))
+}
+
+/// convert the Bunyan record of the request or the identification of a source into a [`SourceEvent`]
+pub fn parse_source_record(record: &Map<String, Value>) -> Option<SourceEvent> {
+    let message = record.get("msg").and_then(Value::as_str)?;
+    let (event_type, url) = source_message(message)?;
+    Some(SourceEvent {
+        event_type,
+        url: url.to_string(),
+        envelope: envelope(record),
+    })
+}
+
+/// the type of the source event a message reports and its url
+fn source_message(message: &str) -> Option<(SourceEventType, &str)> {
+    let (event_type, url) = match message.strip_prefix(REQUEST_MESSAGE) {
+        Some(url) => (SourceEventType::Request, url),
+        None => (
+            SourceEventType::Identification,
+            message.strip_prefix(IDENTIFICATION_MESSAGE)?,
+        ),
     };
+    let url = url.trim();
+    (!url.is_empty() && !url.contains(char::is_whitespace)).then_some((event_type, url))
+}
 
-    if let Some(caps) = RE_EVENT.captures(line) {
-        let event: serde_json::Value = serde_json::from_str(&caps["jsonEvent"])?;
-        let event = event.get("data").unwrap().as_object().unwrap();
-        let one_line_query = RE_QUERY
-            .replace_all(event.get("query").unwrap().as_str().unwrap(), " ")
-            .to_string();
+/// whether a line holds a Bunyan record, which starts with a JSON object or has one with a message
+/// after a prefix, the data of the records of the pretty logger never starts with a quoted key
+pub fn is_bunyan_line(line: &str) -> bool {
+    line.trim_start().starts_with("{\"") || matches!(record(line, |_| true), Ok(Some(_)))
+}
 
-        let event_type = event.get("type").unwrap().as_str().unwrap();
-        let link_event = LinkEvent {
-            link: event.get("link").unwrap().as_object().unwrap().clone(),
-            queue: event.get("queue").unwrap().as_object().unwrap().clone(),
-        };
-        let history_event = if history.contains_key(&one_line_query) {
-            history.get_mut(&one_line_query).unwrap()
-        } else {
-            history.insert(
-                one_line_query.clone(),
-                History {
-                    push: Vec::new(),
-                    pop: Vec::new(),
-                },
-            );
-            history.get_mut(&one_line_query).unwrap()
-        };
+/// convert the Bunyan record of a link queue event into a [`QueryEvent`]
+pub fn parse_record(record: &Map<String, Value>) -> Result<QueryEvent, ParseErrorKind> {
+    let event = record
+        .get("data")
+        .and_then(Value::as_object)
+        .ok_or(ParseErrorKind::MissingField("data"))?;
+    let query = event
+        .get("query")
+        .and_then(Value::as_str)
+        .ok_or(ParseErrorKind::MissingField("data.query"))?;
+    let one_line_query = one_line(query);
+
+    let event_type = event
+        .get("type")
+        .and_then(Value::as_str)
+        .ok_or(ParseErrorKind::MissingField("data.type"))?;
+    let event_type = match event_type {
+        "push" => EventType::Push,
+        "pop" => EventType::Pop,
+        _ => return Err(ParseErrorKind::UnknownEventType(event_type.to_string())),
+    };
+    Ok(QueryEvent {
+        query: one_line_query,
+        execution: 1,
+        event_type,
+        event: LinkEvent {
+            link: typed_field(event, "link", "data.link")?,
+            queue: typed_field(event, "queue", "data.queue")?,
+            envelope: envelope(record),
+        },
+    })
+}
 
-        match event_type {
-            "push" => history_event.push.push(link_event),
-            "pop" => history_event.pop.push(link_event),
-            _ => panic!("there is a link with the unknown event {event_type}"),
+/// The Bunyan record whose message is accepted by `is_wanted` in a line, which may be preceded by a
+/// prefix such as a date or the name of a container and followed by other characters.
+/// Returns `None` when the line has no JSON object, and the error of the first object when none of
+/// the objects of the line is wanted
+fn record(
+    line: &str,
+    is_wanted: impl Fn(&str) -> bool,
+) -> Result<Option<Map<String, Value>>, ParseErrorKind> {
+    let mut first_error = None;
+    for (start, _) in line.match_indices('{') {
+        let mut records =
+            serde_json::Deserializer::from_str(&line[start..]).into_iter::<Map<String, Value>>();
+        match records.next() {
+            Some(Ok(record))
+                if record
+                    .get("msg")
+                    .and_then(Value::as_str)
+                    .is_some_and(&is_wanted) =>
+            {
+                return Ok(Some(record));
+            }
+            Some(Err(error)) if first_error.is_none() => first_error = Some(error),
+            _ => {}
         }
     }
-    Ok(())
+    match first_error {
+        Some(error) => Err(ParseErrorKind::InvalidJson(error)),
+        None => Ok(None),
+    }
+}
+
+/// the Bunyan fields of a record, the fields that do not have the expected type are left out
+fn envelope(record: &Map<String, Value>) -> Envelope {
+    let text = |key: &str| record.get(key).and_then(Value::as_str).map(str::to_string);
+    Envelope {
+        time: text("time"),
+        pid: record.get("pid").and_then(Value::as_u64),
+        hostname: text("hostname"),
+        level: record.get("level").and_then(Value::as_u64),
+        actor: text("actor"),
+        name: text("name"),
+    }
+}
+
+/// replace the line breaks of a query by spaces, queries are identified by their one-line text
+pub fn one_line(query: &str) -> String {
+    lazy_static! {
+        static ref RE_QUERY: Regex = Regex::new(r"(\r\n|\n|\r)").unwrap();
+    };
+    RE_QUERY.replace_all(query, " ").to_string()
+}
+
+/// deserialize the field `key` of an event, `path` is used to report errors
+fn typed_field<T: serde::de::DeserializeOwned>(
+    event: &Map<String, Value>,
+    key: &str,
+    path: &'static str,
+) -> Result<T, ParseErrorKind> {
+    let value = event.get(key).ok_or(ParseErrorKind::MissingField(path))?;
+    serde_json::from_value(value.clone()).map_err(|source| ParseErrorKind::InvalidField {
+        field: path,
+        source,
+    })
 }
 
 #[cfg(test)]
@@ -57,8 +197,6 @@ mod tests {
     use super::*;
 
     lazy_static! {
-        static ref RE_EVENT: Regex =
-            Regex::new(r#".*?(?<jsonEvent>\{.+?"Link queue changed".*\})"#).unwrap();
         static ref RE_QUERY: Regex = Regex::new(r"(\r\n|\n|\r)").unwrap();
     }
 
@@ -66,7 +204,7 @@ mod tests {
     fn should_not_process_an_empty_line() {
         let mut history: HashMap<String, History> = HashMap::new();
         let line = "".to_string();
-        process_line(&line, &mut history).unwrap();
+        assert!(!process_line(&line, &mut history).unwrap());
         assert_eq!(history.len(), 0);
     }
 
@@ -74,7 +212,7 @@ mod tests {
     fn should_not_process_an_unrelated_line() {
         let mut history: HashMap<String, History> = HashMap::new();
         let line = "adwiadawoidqwawio dwadiawjd{ }".to_string();
-        process_line(&line, &mut history).unwrap();
+        assert!(!process_line(&line, &mut history).unwrap());
         assert_eq!(history.len(), 0);
     }
 
@@ -82,7 +220,7 @@ mod tests {
     fn should_not_process_an_unrelated_json() {
         let mut history: HashMap<String, History> = HashMap::new();
         let line = r#"{"name":"comunica","streamProviders":[{"level":"trace"}],"hostname":"bryanelliott-latitude5530","pid":17725,"level":30,"headers":{"accept":"application/n-quads,application/trig;q=0.95,application/ld+json;q=0.9,application/n-triples;q=0.8,text/turtle;q=0.6,application/rdf+xml;q=0.5,text/n3;q=0.35,application/xml;q=0.3,image/svg+xml;q=0.3,text/xml;q=0.3,text/html;q=0.2,application/xhtml+xml;q=0.18,application/json;q=0.135,text/shaclc;q=0.1,text/shaclc-ext;q=0.05","user-agent":"Comunica/actor-http-fetch (Node.js v20.13.1; linux)"},"method":"GET","actor":"urn:comunica:default:http/actors#fetch","msg":"Requesting https://solidbench.linkeddatafragments.org/pods/00000000000000000933/profile/card","time":"2024-07-05T12:06:08.501Z","v":0}"#.to_string();
-        process_line(&line, &mut history).unwrap();
+        assert!(!process_line(&line, &mut history).unwrap());
         assert_eq!(history.len(), 0);
     }
 
@@ -123,13 +261,22 @@ mod tests {
         .unwrap();
 
         let link_event = LinkEvent {
-            link: link.as_object().unwrap().clone(),
-            queue: queue.as_object().unwrap().clone(),
+            link: serde_json::from_value(link).unwrap(),
+            queue: serde_json::from_value(queue).unwrap(),
+            envelope: Envelope {
+                time: Some("2024-07-05T12:06:08.654Z".to_string()),
+                pid: Some(17725),
+                hostname: Some("bryanelliott-latitude5530".to_string()),
+                level: Some(10),
+                actor: None,
+                name: Some("comunica".to_string()),
+            },
         };
 
         let expected_history = History {
             push: vec![link_event],
             pop: Vec::new(),
+            order: vec![EventType::Push],
         };
 
         let expected_history_by_query: HashMap<String, History> =
@@ -138,10 +285,10 @@ mod tests {
         let mut history: HashMap<String, History> = HashMap::new();
         let line = r#"{"name":"comunica","streamProviders":[{"level":"trace"}],"hostname":"bryanelliott-latitude5530","pid":17725,"level":10,"data":{"type":"push","link":{"url":"https://solidbench.linkeddatafragments.org/pods/00000000000000000933/","producedByActor":{"name":"urn:comunica:default:extract-links/actors#predicates-solid","metadata":{"predicates":["http://www.w3.org/ns/pim/space#storage"],"matchingPredicate":"http://www.w3.org/ns/pim/space#storage","checkSubject":true}},"timestamp":2912.4356,"parent":"https://solidbench.linkeddatafragments.org/pods/00000000000000000933/profile/card"},"query":"SELECT ?messageId ?messageCreationDate ?messageContent WHERE {\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/hasCreator> <https://solidbench.linkeddatafragments.org/pods/00000000000000000933/profile/card#me>.\n  ?message <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/Post>.\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/content> ?messageContent.\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/creationDate> ?messageCreationDate.\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/id> ?messageId.\n}","queue":{"size":1,"push":{"urn:comunica:default:extract-links/actors#predicates-solid":1},"pop":{}}},"msg":"Link queue changed","time":"2024-07-05T12:06:08.654Z","v":0}"#.to_string();
 
-        process_line(&line, &mut history).unwrap();
+        assert!(process_line(&line, &mut history).unwrap());
 
         assert_eq!(history.len(), 1);
-        assert!(history.get(&query).is_some());
+        assert!(history.contains_key(&query));
         assert_eq!(
             history, expected_history_by_query,
             "got \n{:?} expected\n {:?}",
@@ -186,13 +333,22 @@ mod tests {
         .unwrap();
 
         let link_event = LinkEvent {
-            link: link.as_object().unwrap().clone(),
-            queue: queue.as_object().unwrap().clone(),
+            link: serde_json::from_value(link).unwrap(),
+            queue: serde_json::from_value(queue).unwrap(),
+            envelope: Envelope {
+                time: Some("2024-07-05T12:06:08.655Z".to_string()),
+                pid: Some(17725),
+                hostname: Some("bryanelliott-latitude5530".to_string()),
+                level: Some(10),
+                actor: None,
+                name: Some("comunica".to_string()),
+            },
         };
 
         let expected_history = History {
             push: Vec::new(),
             pop: vec![link_event],
+            order: vec![EventType::Pop],
         };
 
         let expected_history_by_query: HashMap<String, History> =
@@ -201,10 +357,10 @@ mod tests {
         let mut history: HashMap<String, History> = HashMap::new();
         let line = r#"{"name":"comunica","streamProviders":[{"level":"trace"}],"hostname":"bryanelliott-latitude5530","pid":17725,"level":10,"data":{"type":"pop","link":{"url":"https://solidbench.linkeddatafragments.org/pods/00000000000000000933/","producedByActor":{"name":"urn:comunica:default:extract-links/actors#predicates-solid","metadata":{"predicates":["http://www.w3.org/ns/pim/space#storage"],"matchingPredicate":"http://www.w3.org/ns/pim/space#storage","checkSubject":true}},"timestamp":2913.561066},"query":"SELECT ?messageId ?messageCreationDate ?messageContent WHERE {\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/hasCreator> <https://solidbench.linkeddatafragments.org/pods/00000000000000000933/profile/card#me>.\n  ?message <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/Post>.\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/content> ?messageContent.\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/creationDate> ?messageCreationDate.\n  ?message <https://solidbench.linkeddatafragments.org/www.ldbc.eu/ldbc_socialnet/1.0/vocabulary/id> ?messageId.\n}","queue":{"size":0,"push":{"urn:comunica:default:extract-links/actors#predicates-solid":1},"pop":{"urn:comunica:default:extract-links/actors#predicates-solid":1}}},"msg":"Link queue changed","time":"2024-07-05T12:06:08.655Z","v":0}"#.to_string();
 
-        process_line(&line, &mut history).unwrap();
+        assert!(process_line(&line, &mut history).unwrap());
 
         assert_eq!(history.len(), 1);
-        assert!(history.get(&query).is_some());
+        assert!(history.contains_key(&query));
         assert_eq!(
             history, expected_history_by_query,
             "got \n\n{:?} expected\n\n {:?}",
@@ -258,8 +414,16 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.654Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
         let link_event_push_2 = {
@@ -298,8 +462,16 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.745Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
 
@@ -336,8 +508,16 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.655Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
         let link_event_pop_2 = {
@@ -375,22 +555,34 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.746Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
 
         let expected_history = History {
             push: vec![link_event_push_2],
             pop: vec![link_event_pop_1, link_event_pop_2],
+            order: vec![EventType::Push, EventType::Pop, EventType::Pop],
         };
 
         let expected_history_other_query = History {
             push: vec![link_event_push_1],
             pop: vec![],
+            order: vec![EventType::Push],
         };
-        let expected_history_by_query: HashMap<String, History> =
-            HashMap::from([(query.clone(), expected_history), (other_query.clone(), expected_history_other_query)]);
+        let expected_history_by_query: HashMap<String, History> = HashMap::from([
+            (query.clone(), expected_history),
+            (other_query.clone(), expected_history_other_query),
+        ]);
 
         let mut history: HashMap<String, History> = HashMap::new();
 
@@ -400,8 +592,8 @@ mod tests {
         process_line(&line_pop_2, &mut history).unwrap();
 
         assert_eq!(history.len(), 2);
-        assert!(history.get(&query).is_some());
-        assert!(history.get(&other_query).is_some());
+        assert!(history.contains_key(&query));
+        assert!(history.contains_key(&other_query));
         assert_eq!(
             history, expected_history_by_query,
             "got \n\n{:?} expected\n\n {:?}",
@@ -452,8 +644,16 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.654Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
         let link_event_push_2 = {
@@ -492,8 +692,16 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.745Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
 
@@ -530,8 +738,16 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.655Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
         let link_event_pop_2 = {
@@ -569,14 +785,28 @@ mod tests {
             .unwrap();
 
             LinkEvent {
-                link: link.as_object().unwrap().clone(),
-                queue: queue.as_object().unwrap().clone(),
+                link: serde_json::from_value(link).unwrap(),
+                queue: serde_json::from_value(queue).unwrap(),
+                envelope: Envelope {
+                    time: Some("2024-07-05T12:06:08.746Z".to_string()),
+                    pid: Some(17725),
+                    hostname: Some("bryanelliott-latitude5530".to_string()),
+                    level: Some(10),
+                    actor: None,
+                    name: Some("comunica".to_string()),
+                },
             }
         };
 
         let expected_history = History {
             push: vec![link_event_push_1, link_event_push_2],
             pop: vec![link_event_pop_1, link_event_pop_2],
+            order: vec![
+                EventType::Push,
+                EventType::Push,
+                EventType::Pop,
+                EventType::Pop,
+            ],
         };
         let expected_history_by_query: HashMap<String, History> =
             HashMap::from([(query.clone(), expected_history)]);
@@ -589,11 +819,112 @@ mod tests {
         process_line(&line_pop_2, &mut history).unwrap();
 
         assert_eq!(history.len(), 1);
-        assert!(history.get(&query).is_some());
+        assert!(history.contains_key(&query));
         assert_eq!(
             history, expected_history_by_query,
             "got \n\n{:?} expected\n\n {:?}",
             history, expected_history_by_query
         );
     }
+
+    #[test]
+    fn should_parse_a_line_with_a_prefix() {
+        let record = r#"{"name":"comunica","hostname":"runner","pid":42,"level":10,"actor":"urn:comunica:default:rdf-resolve-hypermedia-links-queue/actors#wrapper-info-occupancy","data":{"type":"push","link":{"url":"https://www.rubensworks.net/","timestamp":1.0},"query":"SELECT * WHERE { ?s ?p ?o }","queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed","time":"2024-07-05T12:06:08.654Z","v":0}"#;
+
+        for line in [
+            record.to_string(),
+            format!("engine-1  | {record}"),
+            format!("[{{worker 2}}] 2024-07-05 12:06:08 {record}\r"),
+        ] {
+            let event = parse_line(&line).unwrap().unwrap();
+            assert_eq!(event.event.link.url, "https://www.rubensworks.net/");
+            assert_eq!(
+                event.event.envelope,
+                Envelope {
+                    time: Some("2024-07-05T12:06:08.654Z".to_string()),
+                    pid: Some(42),
+                    hostname: Some("runner".to_string()),
+                    level: Some(10),
+                    actor: Some("urn:comunica:default:rdf-resolve-hypermedia-links-queue/actors#wrapper-info-occupancy".to_string()),
+                    name: Some("comunica".to_string()),
+                }
+            );
+        }
+        assert!(parse_line("TRACE: Link queue changed").unwrap().is_none());
+    }
+
+    #[test]
+    fn should_parse_the_source_events() {
+        let request = r#"engine-1  | {"name":"comunica","hostname":"runner","pid":42,"level":30,"headers":{"accept":"text/turtle"},"method":"GET","actor":"urn:comunica:default:http/actors#fetch","msg":"Requesting https://www.rubensworks.net/","time":"2024-07-05T12:06:08.501Z","v":0}"#;
+        let identification = r#"{"name":"comunica","level":30,"actor":"urn:comunica:default:query-source-identify-hypermedia/actors#none","msg":"Identified as file source: https://www.rubensworks.net/","time":"2024-07-05T12:06:08.601Z","v":0}"#;
+
+        let request = parse_source_line(request).unwrap();
+        let identification = parse_source_line(identification).unwrap();
+
+        assert_eq!(request.event_type, SourceEventType::Request);
+        assert_eq!(request.url, "https://www.rubensworks.net/");
+        assert_eq!(request.envelope.pid, Some(42));
+        assert_eq!(identification.event_type, SourceEventType::Identification);
+        assert_eq!(
+            identification.envelope.time.as_deref(),
+            Some("2024-07-05T12:06:08.601Z")
+        );
+        assert!(parse_source_line(r#"{"msg":"Requesting a lot of time"}"#).is_none());
+    }
+
+    #[test]
+    fn should_report_invalid_json() {
+        let mut history: HashMap<String, History> = HashMap::new();
+        let line = r#"{"data":{"type":"push",}, "msg":"Link queue changed"}"#;
+        let error = process_line(line, &mut history).unwrap_err();
+        assert!(matches!(error, ParseErrorKind::InvalidJson(_)));
+        assert_eq!(history.len(), 0);
+    }
+
+    #[test]
+    fn should_report_a_record_cut_off_in_its_data() {
+        let mut history: HashMap<String, History> = HashMap::new();
+        let line = r#"{"name":"comunica","pid":17725,"level":10,"data":{"type":"push","link":{"url":"https://www.rubensworks.net/a","#;
+        let error = process_line(line, &mut history).unwrap_err();
+        assert!(matches!(error, ParseErrorKind::InvalidJson(_)));
+        // the records with a message are only parsed when they are link queue events
+        let line =
+            r#"{"name":"comunica","level":30,"msg":"Requesting https://www.rubensworks.net/a","#;
+        assert!(!process_line(line, &mut history).unwrap());
+    }
+
+    #[test]
+    fn should_report_a_missing_field() {
+        let mut history: HashMap<String, History> = HashMap::new();
+        let line = r#"{"data":{"type":"push","link":{"url":"https://www.rubensworks.net/","timestamp":1.0},"queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed"}"#;
+        let error = process_line(line, &mut history).unwrap_err();
+        assert!(matches!(error, ParseErrorKind::MissingField("data.query")));
+        assert_eq!(history.len(), 0);
+    }
+
+    #[test]
+    fn should_report_an_invalid_field() {
+        let mut history: HashMap<String, History> = HashMap::new();
+        let line = r#"{"data":{"type":"push","query":"SELECT * WHERE { ?s ?p ?o }","link":{"timestamp":1.0},"queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed"}"#;
+        let error = process_line(line, &mut history).unwrap_err();
+        assert!(matches!(
+            error,
+            ParseErrorKind::InvalidField {
+                field: "data.link",
+                ..
+            }
+        ));
+        assert_eq!(history.len(), 0);
+    }
+
+    #[test]
+    fn should_report_an_unknown_event_type() {
+        let mut history: HashMap<String, History> = HashMap::new();
+        let line = r#"{"data":{"type":"peek","query":"SELECT * WHERE { ?s ?p ?o }","link":{"url":"https://www.rubensworks.net/","timestamp":1.0},"queue":{"size":1,"push":{},"pop":{}}},"msg":"Link queue changed"}"#;
+        let error = process_line(line, &mut history).unwrap_err();
+        assert!(
+            matches!(error, ParseErrorKind::UnknownEventType(event_type) if event_type == "peek")
+        );
+        assert_eq!(history.len(), 0);
+    }
 }